@@ -0,0 +1,330 @@
+//! A line-based protocol to drive the engine from board GUIs, much like UCI in chess.
+//!
+//! Each command is a line on stdin, and each answer one or more lines on stdout. Positions are
+//! given by GNUbg position ID, with the player on roll as `o`, and decisions are made for them.
+//!
+//! ```text
+//! isready                              readyok
+//! position start | position id <ID>
+//! dice <die> <die>
+//! cube <value> [centered|player|opponent]
+//! score money | score <away> <opponent away> [crawford]
+//! bestmove                             bestmove 8/5 6/5* | bestmove none
+//! cubedecision                         cubedecision <action> nodouble <equity> take <equity>
+//!                                      pass <equity>
+//! rollout [games]                      info games <n> <outcomes>, 9 times as games are
+//!                                      played, then rollout games <n> <outcomes>
+//! quit
+//! ```
+//!
+//! Outcomes are `win`, `wingammon`, `winbackgammon`, `losegammon`, `losebackgammon` and
+//! `equity`, each followed by its value. Commands that fail answer `error <message>`. Rollouts
+//! are cubeless, and both sides play the evaluator's best move.
+
+use std::io::{self, BufRead, Write};
+
+use bg_core::cube::{position_cube_decision, CubeAction, CubeContext};
+use bg_core::dice::Dice;
+use bg_core::eval::{CubeOwnership, Evaluation, Evaluator};
+use bg_core::met::{MatchEquityTable, MatchScore};
+use bg_core::movegen::{find_o_move, generate_o_moves};
+use bg_core::position::Position;
+use bg_core::rollout::rollout_o_games_with;
+use bg_core::search::best_o_play;
+
+const DEFAULT_GAMES: u32 = 10000;
+// Rollouts report their progress this many times.
+const BATCHES: u32 = 10;
+
+pub struct Engine<E: Evaluator> {
+    evaluator: E,
+    met: MatchEquityTable,
+    position: Position,
+    dice: Option<Dice>,
+    cube_value: u32,
+    cube_owner: CubeOwnership,
+    score: Option<MatchScore>,
+}
+
+impl<E: Evaluator> Engine<E> {
+    /// An engine set up for the initial position, at money play, without dice.
+    pub fn make(evaluator: E) -> Engine<E> {
+        Engine {
+            evaluator,
+            met: MatchEquityTable::built_in(),
+            position: Position::initial(),
+            dice: None,
+            cube_value: 1,
+            cube_owner: CubeOwnership::Centered,
+            score: None,
+        }
+    }
+
+    /// Answers commands until `quit` or the end of the input.
+    pub fn run<R: BufRead, W: Write>(&mut self, input: R, output: &mut W) -> io::Result<()> {
+        for line in input.lines() {
+            let line = line?;
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if words.first() == Some(&"quit") {
+                break;
+            }
+            if let Err(message) = self.command(&words, output)? {
+                writeln!(output, "error {}", message)?;
+            }
+            output.flush()?;
+        }
+        Ok(())
+    }
+
+    fn command<W: Write>(
+        &mut self,
+        words: &[&str],
+        output: &mut W,
+    ) -> io::Result<Result<(), String>> {
+        let result = match words {
+            [] => Ok(()),
+            ["isready"] => return writeln!(output, "readyok").map(Ok),
+            ["position", "start"] => {
+                self.position = Position::initial();
+                Ok(())
+            }
+            ["position", "id", id] => Position::from_id(id)
+                .map(|p| self.position = p)
+                .ok_or_else(|| format!("invalid position ID {}", id)),
+            ["dice", first, second] => match (parse_die(first), parse_die(second)) {
+                (Some(first), Some(second)) => {
+                    self.dice = Some(Dice::make(first, second));
+                    Ok(())
+                }
+                _ => Err("dice must be from 1 to 6".to_string()),
+            },
+            ["cube", value] => self.set_cube(value, "centered"),
+            ["cube", value, owner] => self.set_cube(value, owner),
+            ["score", "money"] => {
+                self.score = None;
+                Ok(())
+            }
+            ["score", away, opponent_away, rest @ ..] => {
+                match (away.parse(), opponent_away.parse(), rest) {
+                    (Ok(away @ 1..), Ok(opponent_away @ 1..), []) => {
+                        self.score = Some(MatchScore::make(away, opponent_away, false));
+                        Ok(())
+                    }
+                    (Ok(away @ 1..), Ok(opponent_away @ 1..), ["crawford"]) => {
+                        self.score = Some(MatchScore::make(away, opponent_away, true));
+                        Ok(())
+                    }
+                    _ => Err("invalid score".to_string()),
+                }
+            }
+            ["bestmove"] => return self.best_move(output),
+            ["cubedecision"] => return self.cube_decision(output),
+            ["rollout"] => return self.rollout(DEFAULT_GAMES, output).map(Ok),
+            ["rollout", games] => match games.parse() {
+                Ok(games @ 1..) => return self.rollout(games, output).map(Ok),
+                _ => Err(format!("invalid number of games {}", games)),
+            },
+            [command, ..] => Err(format!("unknown command {}", command)),
+        };
+        Ok(result)
+    }
+
+    fn set_cube(&mut self, value: &str, owner: &str) -> Result<(), String> {
+        let value: u32 = value
+            .parse()
+            .ok()
+            .filter(|v: &u32| v.is_power_of_two())
+            .ok_or_else(|| format!("invalid cube value {}", value))?;
+        self.cube_owner = match owner {
+            "centered" => CubeOwnership::Centered,
+            "player" => CubeOwnership::Player,
+            "opponent" => CubeOwnership::Opponent,
+            _ => return Err(format!("invalid cube owner {}", owner)),
+        };
+        self.cube_value = value;
+        Ok(())
+    }
+
+    fn best_move<W: Write>(&self, output: &mut W) -> io::Result<Result<(), String>> {
+        let dice = match self.dice {
+            Some(dice) => dice,
+            None => return Ok(Err("no dice".to_string())),
+        };
+        if generate_o_moves(&self.position, &dice).is_empty() {
+            return writeln!(output, "bestmove none").map(Ok);
+        }
+
+        let to = best_o_play(&self.evaluator, &self.position, &dice);
+        let m = find_o_move(&self.position, &dice, &to).unwrap();
+        let moves: Vec<String> = m.moves().iter().map(|c| c.to_string()).collect();
+        writeln!(output, "bestmove {}", moves.join(" ")).map(Ok)
+    }
+
+    fn cube_decision<W: Write>(&self, output: &mut W) -> io::Result<Result<(), String>> {
+        let context = match self.score {
+            Some(score) => {
+                CubeContext::match_play(self.cube_value, self.cube_owner, score, &self.met)
+            }
+            None => CubeContext::money(self.cube_value, self.cube_owner),
+        };
+        if !context.can_double() {
+            return Ok(Err("cannot double".to_string()));
+        }
+
+        let decision = position_cube_decision(&self.evaluator, &self.position, 0, &context);
+        let action = match decision.action {
+            CubeAction::NoDouble => "nodouble",
+            CubeAction::DoubleTake => "doubletake",
+            CubeAction::DoublePass => "doublepass",
+            CubeAction::TooGood => "toogood",
+        };
+        writeln!(
+            output,
+            "cubedecision {} nodouble {:.4} take {:.4} pass {:.4}",
+            action, decision.no_double, decision.double_take, decision.double_pass
+        )
+        .map(Ok)
+    }
+
+    // Cubeless, with the evaluator's plays, in batches so that GUIs can show progress.
+    fn rollout<W: Write>(&self, games: u32, output: &mut W) -> io::Result<()> {
+        // Games won, with a gammon and with a backgammon, then lost likewise.
+        let mut counts = [0.0; 5];
+        let mut played = 0;
+
+        for i in 1..=games.min(BATCHES) {
+            let batch_games = games * i / games.min(BATCHES) - played;
+            let stats =
+                rollout_o_games_with(&self.evaluator, &self.position, u64::from(batch_games));
+            let batch = [
+                stats.o_win,
+                stats.o_gammon,
+                stats.o_backgammon,
+                stats.x_gammon,
+                stats.x_backgammon,
+            ];
+            for (count, fraction) in counts.iter_mut().zip(batch.iter()) {
+                *count += (fraction * f64::from(batch_games)).round();
+            }
+            played += batch_games;
+
+            let n = f64::from(played);
+            let total = Evaluation::make(
+                counts[0] / n,
+                counts[1] / n,
+                counts[2] / n,
+                counts[3] / n,
+                counts[4] / n,
+            );
+            let label = if played < games { "info" } else { "rollout" };
+            writeln!(output, "{} games {} {}", label, played, outcomes(&total))?;
+            output.flush()?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_die(s: &str) -> Option<u8> {
+    s.parse().ok().filter(|d| (1..=6).contains(d))
+}
+
+fn outcomes(e: &Evaluation) -> String {
+    format!(
+        "win {:.4} wingammon {:.4} winbackgammon {:.4} losegammon {:.4} losebackgammon {:.4} \
+         equity {:.4}",
+        e.win,
+        e.win_gammon,
+        e.win_backgammon,
+        e.lose_gammon,
+        e.lose_backgammon,
+        e.equity()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bg_core::testing::RaceEvaluator;
+
+    fn run(commands: &str) -> Vec<String> {
+        let mut output = vec![];
+        Engine::make(RaceEvaluator)
+            .run(commands.as_bytes(), &mut output)
+            .unwrap();
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|l| l.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_commands() {
+        let bearoff = Position::make(&[(1, 2)], &[(24, 15)], 0, 0, 13, 0);
+        let output = run(&format!(
+            "isready\n\
+             position id {}\n\
+             bestmove\n\
+             dice 2 1\n\
+             bestmove\n\
+             cube 2 opponent\n\
+             cubedecision\n\
+             position id nonsense\n\
+             frobnicate\n\
+             quit\n\
+             isready\n",
+            bearoff.id()
+        ));
+
+        assert_eq!(
+            vec![
+                "readyok",
+                "error no dice",
+                "bestmove 1/0 1/0",
+                "error cannot double",
+                "error invalid position ID nonsense",
+                "error unknown command frobnicate",
+            ],
+            output
+        );
+    }
+
+    #[test]
+    fn test_cube_decision_and_rollout() {
+        let output = run("position start\nscore 3 5\ncubedecision\n");
+        assert!(output[0].starts_with("cubedecision nodouble "));
+
+        // A close bearoff race, which either side can win but nobody can be gammoned in.
+        let race = Position::make(
+            &[(2, 3), (4, 3), (6, 3)],
+            &[(19, 3), (21, 3), (23, 3)],
+            0,
+            0,
+            6,
+            6,
+        );
+        let output = run(&format!("position id {}\nrollout 100\n", race.id()));
+        assert!(output[0].starts_with("info games 10 win "));
+        assert!(output[8].starts_with("info games 90 win "));
+        assert_eq!(10, output.len());
+
+        let words: Vec<&str> = output[9].split_whitespace().collect();
+        assert_eq!(["rollout", "games", "100", "win"], words[..4]);
+        let value = |i: usize| words[i].parse::<f64>().unwrap();
+        assert!(0.0 < value(4) && value(4) < 1.0);
+        assert_eq!(0.0, value(6) + value(8) + value(10) + value(12));
+        assert!((2.0 * value(4) - 1.0 - value(14)).abs() < 1e-3);
+
+        let bearoff = Position::make(&[(1, 2)], &[(24, 15)], 0, 0, 13, 0);
+        let output = run(&format!("position id {}\nrollout 7\n", bearoff.id()));
+        assert_eq!(
+            vec![
+                "info games 1 win 1.0000 wingammon 1.0000 winbackgammon 0.0000 losegammon 0.0000 \
+                 losebackgammon 0.0000 equity 2.0000",
+                "rollout games 7 win 1.0000 wingammon 1.0000 winbackgammon 0.0000 losegammon \
+                 0.0000 losebackgammon 0.0000 equity 2.0000",
+            ],
+            output[..1].iter().chain(output.last()).collect::<Vec<_>>()
+        );
+    }
+}
@@ -1,48 +1,208 @@
+mod engine;
+
 use std::env;
 use std::fs;
+use std::io;
+use std::net::TcpListener;
+use std::process;
 use std::time;
 
+use bg_core::bearoff::{BearoffDatabase, BearoffEvaluator};
 use bg_core::dice::Dice;
-use bg_core::game::Match;
+use bg_core::eval::RolloutEvaluator;
+use bg_core::game::{Cube, Match};
 use bg_core::movegen::{generate_o_moves, generate_x_moves};
-use bg_core::position::Position;
+use bg_core::policy::{EvaluatorPolicy, Policy};
+use bg_core::position::{Position, Side};
 use bg_core::rollout;
 
-use bg_parser::parse_match;
+use bg_fibs::serve_external;
+use bg_parser::{parse_match, read_archive, validate};
+use engine::Engine;
 
 use rand::Rng;
 
 fn load_match(filename: &str) -> Match {
     let contents = fs::read_to_string(filename).expect("Something went wrong reading the file.");
-    parse_match(&contents).unwrap()
+    match parse_match(&contents) {
+        Ok(m) => m,
+        Err(e) => {
+            eprintln!("{}: {}", filename, e);
+            process::exit(1);
+        }
+    }
+}
+
+// Usage: `bg-cli bearoff <output> [checkers] [--cubeful]`
+fn generate_bearoff(args: &[String]) {
+    let output = &args[0];
+    let checkers: u8 = args
+        .get(1)
+        .filter(|a| !a.starts_with("--"))
+        .map(|a| a.parse().expect("Invalid number of checkers."))
+        .unwrap_or(6);
+    let cubeful = args.iter().any(|a| a == "--cubeful");
+
+    let start = time::Instant::now();
+    let db = BearoffDatabase::generate(6, checkers, cubeful);
+    println!(
+        "Generated bearoff database for {} checkers in {:.1}s",
+        checkers,
+        start.elapsed().as_secs_f64()
+    );
+
+    db.save(output).expect("Something went wrong writing the file.");
+}
+
+// Usage: `bg-cli validate <match>`
+fn validate_match(args: &[String]) {
+    let diagnostics = validate(&load_match(&args[0]));
+
+    for diagnostic in &diagnostics {
+        println!("{}", diagnostic);
+    }
+
+    if !diagnostics.is_empty() {
+        process::exit(1);
+    }
+}
+
+// Usage: `bg-cli archive <file or directory>`
+fn read_matches(args: &[String]) {
+    let (mut read, mut broken) = (0, 0);
+
+    for entry in read_archive(&args[0]) {
+        match entry.result {
+            Ok(m) => {
+                read += 1;
+                let players = m
+                    .games
+                    .first()
+                    .map(|g| format!("{} - {}", g.o_player, g.x_player))
+                    .unwrap_or_default();
+                println!(
+                    "{}:{}: {} point match, {}, {} games",
+                    entry.path.display(),
+                    entry.line,
+                    m.length,
+                    players,
+                    m.games.len()
+                );
+            }
+            Err(e) => {
+                broken += 1;
+                eprintln!("{}:{}: {}", entry.path.display(), entry.line, e);
+            }
+        }
+    }
+
+    println!("{} matches read, {} broken", read, broken);
+}
+
+// Usage: `bg-cli external <port> [bearoff database]`
+//
+// Plays for GNUbg, set up with `set player <name> external localhost:<port>`.
+fn external_player(args: &[String]) {
+    let port: u16 = args[0].parse().expect("Invalid port.");
+    match args.get(1) {
+        Some(path) => {
+            let database =
+                BearoffDatabase::load(path).expect("Something went wrong reading the file.");
+            let evaluator = BearoffEvaluator::make(database, RolloutEvaluator);
+            serve_external_player(port, EvaluatorPolicy::make(evaluator, 0));
+        }
+        None => serve_external_player(port, EvaluatorPolicy::make(RolloutEvaluator, 0)),
+    }
+}
+
+fn serve_external_player<P: Policy>(port: u16, mut policy: P) {
+    let listener = TcpListener::bind(("127.0.0.1", port)).expect("Could not listen on the port.");
+    println!("Waiting for GNUbg on port {}", port);
+
+    for stream in listener.incoming() {
+        let result = stream.and_then(|stream| {
+            println!("GNUbg connected");
+            serve_external(stream, &mut policy)
+        });
+        match result {
+            Ok(()) => println!("GNUbg disconnected"),
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+}
+
+// Usage: `bg-cli engine [bearoff database]`
+//
+// Speaks the protocol described in `engine.rs` on stdin and stdout.
+fn run_engine(args: &[String]) {
+    let (stdin, stdout) = (io::stdin(), io::stdout());
+    let result = match args.first() {
+        Some(path) => {
+            let database =
+                BearoffDatabase::load(path).expect("Something went wrong reading the file.");
+            let evaluator = BearoffEvaluator::make(database, RolloutEvaluator);
+            Engine::make(evaluator).run(stdin.lock(), &mut stdout.lock())
+        }
+        None => Engine::make(RolloutEvaluator).run(stdin.lock(), &mut stdout.lock()),
+    };
+    result.expect("Something went wrong reading commands.");
 }
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    let mtch = load_match(&args[1]);
 
-    let g1 = &mtch.games[0];
+    if args[1] == "bearoff" {
+        generate_bearoff(&args[2..]);
+        return;
+    }
 
-    let mut p = Position::initial();
-    println!("{}", p);
+    if args[1] == "archive" {
+        read_matches(&args[2..]);
+        return;
+    }
+
+    if args[1] == "engine" {
+        run_engine(&args[2..]);
+        return;
+    }
 
-    for turn in &g1.turns {
-        let o_move = &turn.0;
-        let x_move = &turn.1;
+    if args[1] == "external" {
+        external_player(&args[2..]);
+        return;
+    }
 
-        for om in o_move {
-            p = p.apply_o_move(om);
-            println!("o -> {}", om);
-            println!("{}", p);
-        }
+    if args[1] == "validate" {
+        validate_match(&args[2..]);
+        return;
+    }
 
-        for xm in x_move {
-            p = p.apply_x_move(xm);
-            println!("x -> {}", xm);
-            println!("{}", p);
+    let mtch = load_match(&args[1]);
+
+    let g1 = &mtch.games[0];
+
+    let mut cube = Cube::initial();
+    println!("{}", Position::initial());
+
+    for step in g1.replay() {
+        match step {
+            Ok(step) => {
+                let side = match step.side {
+                    Side::O => "o",
+                    Side::X => "x",
+                };
+                println!("{} -> {}", side, step.action);
+                println!("{}", step.after);
+                cube = step.cube;
+            }
+            Err(e) => {
+                println!("{}", e);
+                return;
+            }
         }
     }
 
+    println!("Cube: {:?}", cube);
+
     if 2 < 3 {
         return;
     }
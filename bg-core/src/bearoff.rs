@@ -0,0 +1,534 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, Write};
+use std::path::Path;
+
+use super::dice::all_rolls;
+use super::eval::{CubeOwnership, Evaluation, Evaluator};
+use super::movegen::generate_o_moves;
+use super::position::Position;
+
+const MAGIC: &[u8; 8] = b"BGBEAR03";
+
+/// The largest number of points a side can have checkers on.
+pub const MAX_POINTS: u8 = 6;
+
+/// The largest number of checkers per side. With at most 14 checkers left, neither side
+/// can be gammoned, so the cubeless equity is determined by the winning chances alone.
+pub const MAX_CHECKERS: u8 = 14;
+
+/// Checkers per point for one side, indexed from the bearing-off point. Unused points are 0.
+type Layout = [u8; MAX_POINTS as usize];
+
+/// An exact two-sided bearoff database.
+///
+/// Covers every position where both players have at most `checkers` checkers left, all on
+/// their `points` lowest home points. Values are stored for the player on roll: winning
+/// chances, and optionally money cubeful equities without doubling for each cube ownership
+/// (normalized to a cube value of 1), from which the cube decisions follow.
+pub struct BearoffDatabase {
+    points: u8,
+    checkers: u8,
+    sides: Vec<Layout>,
+    index: HashMap<Layout, usize>,
+    win: Vec<f32>,
+    cubeful: Option<Vec<f32>>,
+}
+
+impl BearoffDatabase {
+    /// Computes the database from scratch. The size grows quickly: 6 checkers on 6 points
+    /// is 924 configurations per side, i.e. about 850k positions.
+    pub fn generate(points: u8, checkers: u8, cubeful: bool) -> BearoffDatabase {
+        assert!((1..=MAX_POINTS).contains(&points));
+        assert!((1..=MAX_CHECKERS).contains(&checkers));
+
+        let sides = enumerate_sides(points, checkers);
+        let index: HashMap<Layout, usize> =
+            sides.iter().enumerate().map(|(i, s)| (*s, i)).collect();
+        let n = sides.len();
+
+        let rolls = all_rolls();
+
+        // For every configuration and every roll, the configurations reachable by a legal play.
+        let successors: Vec<Vec<Vec<usize>>> = sides
+            .iter()
+            .map(|side| {
+                rolls
+                    .iter()
+                    .map(|(dice, _)| {
+                        generate_o_moves(&side_to_position(side), dice)
+                            .iter()
+                            .map(|p| index[&position_to_side(p)])
+                            .collect()
+                    })
+                    .collect()
+            })
+            .collect();
+
+        // Every play strictly reduces the pip count of the player on roll, so processing pairs
+        // by increasing total pip count guarantees successors are known before they're needed.
+        let pips: Vec<u32> = sides.iter().map(pip_count).collect();
+        let mut pairs: Vec<(usize, usize)> =
+            (1..n).flat_map(|i| (1..n).map(move |j| (i, j))).collect();
+        pairs.sort_by_key(|&(i, j)| pips[i] + pips[j]);
+
+        let mut win = vec![0.0f32; n * n];
+        let mut cubeful_values = if cubeful {
+            Some(vec![0.0f32; n * n * 3])
+        } else {
+            None
+        };
+
+        for &(i, j) in pairs.iter() {
+            let mut p_win = 0.0;
+            let mut no_double = [0.0; 3];
+
+            for (r, (_, weight)) in rolls.iter().enumerate() {
+                let mut best_win: f64 = 0.0;
+                let mut best_no_double = [f64::NEG_INFINITY; 3];
+
+                for &k in successors[i][r].iter() {
+                    if k == 0 {
+                        best_win = 1.0;
+                        best_no_double = [1.0; 3];
+                        break;
+                    }
+
+                    best_win = best_win.max(1.0 - f64::from(win[j * n + k]));
+
+                    if let Some(values) = &cubeful_values {
+                        for o in OWNERSHIPS.iter() {
+                            let theirs = best_equity(values, (j * n + k) * 3, o.invert());
+                            let e = &mut best_no_double[slot(*o)];
+                            *e = e.max(-theirs);
+                        }
+                    }
+                }
+
+                p_win += weight * best_win;
+                for o in 0..3 {
+                    no_double[o] += weight * best_no_double[o];
+                }
+            }
+
+            win[i * n + j] = p_win as f32;
+
+            if let Some(values) = &mut cubeful_values {
+                for o in OWNERSHIPS.iter() {
+                    values[(i * n + j) * 3 + slot(*o)] = no_double[slot(*o)] as f32;
+                }
+            }
+        }
+
+        BearoffDatabase {
+            points,
+            checkers,
+            sides,
+            index,
+            win,
+            cubeful: cubeful_values,
+        }
+    }
+
+    pub fn points(&self) -> u8 {
+        self.points
+    }
+
+    pub fn checkers(&self) -> u8 {
+        self.checkers
+    }
+
+    pub fn has_cubeful(&self) -> bool {
+        self.cubeful.is_some()
+    }
+
+    /// Whether `position` is a pure bearoff covered by this database.
+    pub fn contains(&self, position: &Position) -> bool {
+        self.indices(position).is_some()
+    }
+
+    /// The probability that `o`, on roll, wins.
+    pub fn win_probability(&self, position: &Position) -> Option<f64> {
+        let (i, j) = self.indices(position)?;
+        Some(f64::from(self.win[i * self.sides.len() + j]))
+    }
+
+    /// The cubeless equity for `o`, on roll.
+    pub fn cubeless_equity(&self, position: &Position) -> Option<f64> {
+        self.win_probability(position).map(|p| 2.0 * p - 1.0)
+    }
+
+    /// The money cubeful equity for `o`, on roll, after the best cube action, normalized to a
+    /// cube value of 1. Only available if the database was generated with cubeful values.
+    pub fn cubeful_equity(&self, position: &Position, cube: CubeOwnership) -> Option<f64> {
+        let values = self.cubeful.as_ref()?;
+        let (i, j) = self.indices(position)?;
+        Some(best_equity(values, (i * self.sides.len() + j) * 3, cube))
+    }
+
+    /// The money cubeful equities for `o`, on roll, if `o` doesn't double and if `o` doubles and
+    /// `x` takes, normalized to a cube value of 1. Only available if the database was generated
+    /// with cubeful values.
+    pub fn cube_equities(&self, position: &Position, cube: CubeOwnership) -> Option<(f64, f64)> {
+        let values = self.cubeful.as_ref()?;
+        let (i, j) = self.indices(position)?;
+        let offset = (i * self.sides.len() + j) * 3;
+        Some((
+            f64::from(values[offset + slot(cube)]),
+            double_take_equity(values, offset),
+        ))
+    }
+
+    fn indices(&self, position: &Position) -> Option<(usize, usize)> {
+        if position.o_bar_value() > 0 || position.x_bar_value() > 0 || position.is_over() {
+            return None;
+        }
+
+        let o_side = self.side_of(position)?;
+        let x_side = self.side_of(&position.flip())?;
+
+        Some((self.index[&o_side], self.index[&x_side]))
+    }
+
+    // The configuration of `o` in `position`, if it fits in the database.
+    fn side_of(&self, position: &Position) -> Option<Layout> {
+        if (self.points..24).any(|pt| position.point_o_value(pt) > 0) {
+            return None;
+        }
+
+        let side = position_to_side(position);
+
+        if side.iter().sum::<u8>() > self.checkers {
+            return None;
+        }
+
+        Some(side)
+    }
+
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_all(MAGIC)?;
+        w.write_all(&[self.points, self.checkers, self.has_cubeful() as u8])?;
+
+        for v in self.win.iter() {
+            w.write_all(&v.to_le_bytes())?;
+        }
+
+        if let Some(values) = &self.cubeful {
+            for v in values.iter() {
+                w.write_all(&v.to_le_bytes())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn read_from<R: Read>(r: &mut R) -> io::Result<BearoffDatabase> {
+        let mut magic = [0u8; 8];
+        r.read_exact(&mut magic)?;
+
+        if &magic != MAGIC {
+            return Err(invalid_data("not a bearoff database"));
+        }
+
+        let mut header = [0u8; 3];
+        r.read_exact(&mut header)?;
+        let [points, checkers, cubeful] = header;
+        let n = check_header(points, checkers, cubeful)?;
+
+        // Values are read as they come, so a short file can't make us allocate a lot.
+        let win = read_f32s(r, n * n)?;
+        let cubeful = if cubeful != 0 {
+            Some(read_f32s(r, n * n * 3)?)
+        } else {
+            None
+        };
+
+        let sides = enumerate_sides(points, checkers);
+        let index: HashMap<Layout, usize> =
+            sides.iter().enumerate().map(|(i, s)| (*s, i)).collect();
+
+        Ok(BearoffDatabase {
+            points,
+            checkers,
+            sides,
+            index,
+            win,
+            cubeful,
+        })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        self.write_to(&mut w)?;
+        w.flush()
+    }
+
+    /// Reads a database saved with `save`, after checking that the file is as large as its
+    /// header says.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<BearoffDatabase> {
+        let mut file = File::open(path)?;
+        let mut start = [0u8; 11];
+        file.read_exact(&mut start)?;
+        if &start[..8] != MAGIC {
+            return Err(invalid_data("not a bearoff database"));
+        }
+        let n = check_header(start[8], start[9], start[10])?;
+        let values = if start[10] != 0 { 4 } else { 1 };
+        if file.metadata()?.len() != (start.len() + n * n * values * 4) as u64 {
+            return Err(invalid_data(
+                "bearoff database size doesn't match its header",
+            ));
+        }
+
+        file.rewind()?;
+        BearoffDatabase::read_from(&mut BufReader::new(file))
+    }
+}
+
+/// Uses the bearoff database whenever it applies, and another evaluator otherwise.
+pub struct BearoffEvaluator<E: Evaluator> {
+    database: BearoffDatabase,
+    fallback: E,
+}
+
+impl<E: Evaluator> BearoffEvaluator<E> {
+    pub fn make(database: BearoffDatabase, fallback: E) -> BearoffEvaluator<E> {
+        BearoffEvaluator { database, fallback }
+    }
+
+    pub fn database(&self) -> &BearoffDatabase {
+        &self.database
+    }
+}
+
+impl<E: Evaluator> Evaluator for BearoffEvaluator<E> {
+    fn evaluate(&self, position: &Position) -> Evaluation {
+        match self.database.win_probability(position) {
+            Some(p) => Evaluation::make(p, 0.0, 0.0, 0.0, 0.0),
+            None => self.fallback.evaluate(position),
+        }
+    }
+
+    fn money_cube_equities(&self, position: &Position, cube: CubeOwnership) -> Option<(f64, f64)> {
+        if self.database.contains(position) {
+            self.database.cube_equities(position, cube)
+        } else {
+            self.fallback.money_cube_equities(position, cube)
+        }
+    }
+}
+
+const OWNERSHIPS: [CubeOwnership; 3] = [
+    CubeOwnership::Centered,
+    CubeOwnership::Player,
+    CubeOwnership::Opponent,
+];
+
+fn slot(cube: CubeOwnership) -> usize {
+    match cube {
+        CubeOwnership::Centered => 0,
+        CubeOwnership::Player => 1,
+        CubeOwnership::Opponent => 2,
+    }
+}
+
+// The equity after doubling and the opponent taking, from the no-double equities at `offset`.
+fn double_take_equity(values: &[f32], offset: usize) -> f64 {
+    2.0 * f64::from(values[offset + slot(CubeOwnership::Opponent)])
+}
+
+// The equity with the best cube action: doubling if allowed and better, and the opponent then
+// taking or passing.
+fn best_equity(values: &[f32], offset: usize, cube: CubeOwnership) -> f64 {
+    let no_double = f64::from(values[offset + slot(cube)]);
+    match cube {
+        CubeOwnership::Opponent => no_double,
+        _ => no_double.max(double_take_equity(values, offset).min(1.0)),
+    }
+}
+
+// The number of configurations per side, if the header describes a database we can read.
+fn check_header(points: u8, checkers: u8, cubeful: u8) -> io::Result<usize> {
+    if !(1..=MAX_POINTS).contains(&points) || !(1..=MAX_CHECKERS).contains(&checkers) {
+        return Err(invalid_data("unsupported bearoff database dimensions"));
+    }
+    if cubeful > 1 {
+        return Err(invalid_data("invalid bearoff database header"));
+    }
+    // Placing at most `checkers` checkers on `points` points: C(points + checkers, points).
+    let (points, checkers) = (usize::from(points), usize::from(checkers));
+    Ok((1..=points).fold(1, |n, k| n * (checkers + k) / k))
+}
+
+// All ways of placing at most `checkers` checkers on `points` points. The empty side comes first.
+fn enumerate_sides(points: u8, checkers: u8) -> Vec<Layout> {
+    fn fill(side: &mut Layout, point: usize, points: usize, left: u8, acc: &mut Vec<Layout>) {
+        if point == points {
+            acc.push(*side);
+            return;
+        }
+        for count in 0..=left {
+            side[point] = count;
+            fill(side, point + 1, points, left - count, acc);
+        }
+        side[point] = 0;
+    }
+
+    let mut sides = Vec::new();
+    fill(
+        &mut [0; MAX_POINTS as usize],
+        0,
+        usize::from(points),
+        checkers,
+        &mut sides,
+    );
+    sides
+}
+
+fn pip_count(side: &Layout) -> u32 {
+    side.iter()
+        .enumerate()
+        .map(|(i, &c)| (i as u32 + 1) * u32::from(c))
+        .sum()
+}
+
+// A position with `o` checkers as in `side`, and `x` already done. There's no contact, so
+// `x` doesn't influence `o`'s moves.
+fn side_to_position(side: &Layout) -> Position {
+    let o_points: Vec<(u8, u8)> = side
+        .iter()
+        .enumerate()
+        .filter(|(_, &c)| c > 0)
+        .map(|(i, &c)| (i as u8 + 1, c))
+        .collect();
+    let on_board: u8 = side.iter().sum();
+
+    Position::make(&o_points, &[], 0, 0, 15 - on_board, 15)
+}
+
+fn position_to_side(position: &Position) -> Layout {
+    let mut side = [0; MAX_POINTS as usize];
+    for (i, c) in side.iter_mut().enumerate() {
+        *c = position.point_o_value(i as u8);
+    }
+    side
+}
+
+fn read_f32s<R: Read>(r: &mut R, count: usize) -> io::Result<Vec<f32>> {
+    let mut bytes = Vec::new();
+    r.take(count as u64 * 4).read_to_end(&mut bytes)?;
+    if bytes.len() != count * 4 {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect())
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cube::{position_cube_decision, CubeAction, CubeContext};
+    use crate::eval::RolloutEvaluator;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-6
+    }
+
+    #[test]
+    fn test_bearoff_one_checker_each() {
+        let db = BearoffDatabase::generate(6, 2, true);
+
+        // `o` needs a 6, a sum of 6 or more, or doubles 2-2 and up: 27 rolls out of 36.
+        let p = Position::make(&[(6, 1)], &[(24, 1)], 0, 0, 14, 14);
+        assert!(close(0.75, db.win_probability(&p).unwrap()));
+        assert!(close(0.5, db.cubeless_equity(&p).unwrap()));
+
+        // With access to the cube, `o` doubles and `x` is indifferent between taking and passing.
+        assert!(close(
+            1.0,
+            db.cubeful_equity(&p, CubeOwnership::Centered).unwrap()
+        ));
+        assert!(close(
+            0.5,
+            db.cubeful_equity(&p, CubeOwnership::Opponent).unwrap()
+        ));
+
+        // Always bearing off.
+        let p = Position::make(&[(1, 2)], &[(24, 1)], 0, 0, 13, 14);
+        assert!(close(1.0, db.win_probability(&p).unwrap()));
+    }
+
+    #[test]
+    fn test_bearoff_cube_decision() {
+        let db = BearoffDatabase::generate(6, 2, true);
+        let evaluator = BearoffEvaluator::make(db, RolloutEvaluator);
+
+        // 75% to win, and no market left to lose: `x` can take or pass.
+        let p = Position::make(&[(6, 1)], &[(24, 1)], 0, 0, 14, 14);
+        let decision = position_cube_decision(
+            &evaluator,
+            &p,
+            0,
+            &CubeContext::money(1, CubeOwnership::Centered),
+        );
+        assert!(close(1.0, decision.double_take));
+        assert!(decision.no_double < 1.0);
+        assert_eq!(CubeAction::DoublePass, decision.action);
+
+        // Outside the database, the model decides.
+        assert!(evaluator
+            .money_cube_equities(&Position::initial(), CubeOwnership::Centered)
+            .is_none());
+    }
+
+    #[test]
+    fn test_bearoff_coverage() {
+        let db = BearoffDatabase::generate(3, 2, false);
+
+        assert!(!db.contains(&Position::initial()));
+        assert!(!db.contains(&Position::make(&[(4, 1)], &[(24, 1)], 0, 0, 14, 14)));
+        assert!(!db.contains(&Position::make(&[(1, 3)], &[(24, 1)], 0, 0, 12, 14)));
+        assert!(db.contains(&Position::make(&[(3, 1)], &[(22, 2)], 0, 0, 14, 13)));
+        assert!(db
+            .cubeful_equity(
+                &Position::make(&[(3, 1)], &[(22, 2)], 0, 0, 14, 13),
+                CubeOwnership::Centered
+            )
+            .is_none());
+    }
+
+    #[test]
+    fn test_bearoff_round_trip() {
+        let db = BearoffDatabase::generate(4, 2, true);
+        let mut bytes: Vec<u8> = Vec::new();
+        db.write_to(&mut bytes).unwrap();
+
+        let loaded = BearoffDatabase::read_from(&mut bytes.as_slice()).unwrap();
+        assert_eq!(db.win, loaded.win);
+        assert_eq!(db.cubeful, loaded.cubeful);
+
+        assert!(BearoffDatabase::read_from(&mut &b"nonsense"[..]).is_err());
+
+        // A header promising the largest database, without the values.
+        let mut huge = MAGIC.to_vec();
+        huge.extend_from_slice(&[MAX_POINTS, MAX_CHECKERS, 1]);
+        assert!(BearoffDatabase::read_from(&mut huge.as_slice()).is_err());
+
+        let path = std::env::temp_dir().join(format!("bearoff-{}.db", std::process::id()));
+        std::fs::write(&path, &huge).unwrap();
+        let error = BearoffDatabase::load(&path).err().unwrap();
+        assert_eq!(
+            "bearoff database size doesn't match its header",
+            error.to_string()
+        );
+        db.save(&path).unwrap();
+        assert_eq!(db.win, BearoffDatabase::load(&path).unwrap().win);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
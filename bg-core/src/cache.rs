@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use super::eval::{CubeOwnership, Evaluation, Evaluator};
+use super::position::Position;
+
+/// What an evaluation depends on: the position, with `o` on roll, and how deep we looked.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub struct CacheKey {
+    pub position: Position,
+    pub ply: u8,
+}
+
+impl CacheKey {
+    pub fn make(position: Position, ply: u8) -> CacheKey {
+        CacheKey { position, ply }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+}
+
+impl CacheStats {
+    pub fn lookups(&self) -> u64 {
+        self.hits + self.misses
+    }
+
+    pub fn hit_rate(&self) -> f64 {
+        if self.lookups() == 0 {
+            0.0
+        } else {
+            self.hits as f64 / self.lookups() as f64
+        }
+    }
+}
+
+// Entries live in two generations. New entries go into `current`; when it's full, it becomes
+// `previous` and the old `previous` is dropped. Hits in `previous` are promoted, so frequently
+// used entries survive, and the total size never exceeds the capacity.
+struct Generations {
+    current: HashMap<CacheKey, Evaluation>,
+    previous: HashMap<CacheKey, Evaluation>,
+}
+
+/// A bounded, thread-safe map from positions to evaluations.
+pub struct EvalCache {
+    generation_size: usize,
+    generations: Mutex<Generations>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl EvalCache {
+    /// A cache holding at most `capacity` evaluations.
+    pub fn make(capacity: usize) -> EvalCache {
+        EvalCache {
+            generation_size: (capacity / 2).max(1),
+            generations: Mutex::new(Generations {
+                current: HashMap::new(),
+                previous: HashMap::new(),
+            }),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn get(&self, key: &CacheKey) -> Option<Evaluation> {
+        let mut generations = self.generations.lock().unwrap();
+
+        let found = match generations.current.get(key) {
+            Some(evaluation) => Some(*evaluation),
+            None => {
+                let promoted = generations.previous.remove(key);
+                if let Some(evaluation) = promoted {
+                    self.insert_locked(&mut generations, *key, evaluation);
+                }
+                promoted
+            }
+        };
+
+        let counter = if found.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+
+        found
+    }
+
+    pub fn insert(&self, key: CacheKey, evaluation: Evaluation) {
+        let mut generations = self.generations.lock().unwrap();
+        self.insert_locked(&mut generations, key, evaluation);
+    }
+
+    fn insert_locked(&self, generations: &mut Generations, key: CacheKey, evaluation: Evaluation) {
+        if generations.current.len() >= self.generation_size
+            && !generations.current.contains_key(&key)
+        {
+            generations.previous = std::mem::take(&mut generations.current);
+        }
+        generations.current.insert(key, evaluation);
+    }
+
+    pub fn clear(&self) {
+        let mut generations = self.generations.lock().unwrap();
+        generations.current.clear();
+        generations.previous.clear();
+        self.hits.store(0, Ordering::Relaxed);
+        self.misses.store(0, Ordering::Relaxed);
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let generations = self.generations.lock().unwrap();
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: generations.current.len() + generations.previous.len(),
+        }
+    }
+}
+
+/// Wraps any evaluator with an `EvalCache`. Besides static evaluations, the cache also keeps
+/// the deeper evaluations computed by `search`.
+pub struct CachedEvaluator<E: Evaluator> {
+    evaluator: E,
+    cache: EvalCache,
+}
+
+impl<E: Evaluator> CachedEvaluator<E> {
+    pub fn make(evaluator: E, capacity: usize) -> CachedEvaluator<E> {
+        CachedEvaluator {
+            evaluator,
+            cache: EvalCache::make(capacity),
+        }
+    }
+
+    pub fn cache(&self) -> &EvalCache {
+        &self.cache
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.cache.stats()
+    }
+}
+
+impl<E: Evaluator> Evaluator for CachedEvaluator<E> {
+    fn evaluate(&self, position: &Position) -> Evaluation {
+        let key = CacheKey::make(*position, 0);
+
+        if let Some(evaluation) = self.cache.get(&key) {
+            return evaluation;
+        }
+
+        let evaluation = self.evaluator.evaluate(position);
+        self.cache.insert(key, evaluation);
+        evaluation
+    }
+
+    fn cached(&self, key: &CacheKey) -> Option<Evaluation> {
+        self.cache.get(key)
+    }
+
+    fn store(&self, key: CacheKey, evaluation: Evaluation) {
+        self.cache.insert(key, evaluation);
+    }
+
+    fn money_cube_equities(&self, position: &Position, cube: CubeOwnership) -> Option<(f64, f64)> {
+        self.evaluator.money_cube_equities(position, cube)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dice::Dice;
+    use crate::search::{rank_o_plays, RankedPlay, SearchConfig};
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+    use std::sync::atomic::AtomicUsize;
+
+    // Arbitrary but deterministic evaluations, counting how often it's called.
+    struct Counting(AtomicUsize);
+
+    impl Evaluator for Counting {
+        fn evaluate(&self, position: &Position) -> Evaluation {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            let mut hasher = DefaultHasher::new();
+            position.hash(&mut hasher);
+            let win = (hasher.finish() % 1000) as f64 / 1000.0;
+            Evaluation::make(win, 0.0, 0.0, 0.0, 0.0)
+        }
+    }
+
+    #[test]
+    fn test_cache_hits() {
+        let evaluator = CachedEvaluator::make(Counting(AtomicUsize::new(0)), 1000);
+        let p = Position::initial();
+
+        let e1 = evaluator.evaluate(&p);
+        let e2 = evaluator.evaluate(&p);
+
+        assert_eq!(e1, e2);
+        assert_eq!(1, evaluator.evaluator.0.load(Ordering::Relaxed));
+        assert_eq!(
+            CacheStats {
+                hits: 1,
+                misses: 1,
+                entries: 1
+            },
+            evaluator.stats()
+        );
+        assert!((0.5 - evaluator.stats().hit_rate()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_cache_bounded() {
+        let cache = EvalCache::make(10);
+        let e = Evaluation::make(0.5, 0.0, 0.0, 0.0, 0.0);
+
+        for ply in 0..100 {
+            cache.insert(CacheKey::make(Position::initial(), ply), e);
+            assert!(cache.stats().entries <= 10);
+        }
+
+        assert!(cache
+            .get(&CacheKey::make(Position::initial(), 99))
+            .is_some());
+        assert!(cache.get(&CacheKey::make(Position::initial(), 0)).is_none());
+    }
+
+    #[test]
+    fn test_cache_search() {
+        let evaluator = CachedEvaluator::make(Counting(AtomicUsize::new(0)), 100_000);
+        let config = SearchConfig::make(1, vec![]);
+
+        let first = rank_o_plays(&evaluator, &Position::initial(), &Dice::make(3, 1), &config);
+        let calls = evaluator.evaluator.0.load(Ordering::Relaxed);
+        let second = rank_o_plays(&evaluator, &Position::initial(), &Dice::make(3, 1), &config);
+
+        let equities =
+            |plays: &Vec<RankedPlay>| plays.iter().map(|p| p.equity).collect::<Vec<f64>>();
+        assert_eq!(equities(&first), equities(&second));
+        assert_eq!(calls, evaluator.evaluator.0.load(Ordering::Relaxed));
+        assert!(evaluator.stats().hits > 0);
+    }
+}
@@ -0,0 +1,426 @@
+use super::dice::all_rolls;
+use super::eval::{CubeOwnership, Evaluation, Evaluator};
+use super::met::{mwc_after, MatchEquity, MatchScore};
+use super::position::Position;
+use super::search::{best_o_play, evaluate_on_roll};
+
+/// A typical cube life index for middle-game positions.
+pub const DEFAULT_CUBE_LIFE: f64 = 0.68;
+
+/// Everything about the cube that matters for a decision, from the point of view of the player
+/// on roll.
+#[derive(Clone, Copy)]
+pub struct CubeContext<'a> {
+    pub value: u32,
+    pub owner: CubeOwnership,
+    /// How efficient the cube is, from 0 (dead cube) to 1 (fully live cube).
+    pub cube_life: f64,
+    /// For match play, the score and how to convert it into match winning chances.
+    pub score: Option<(MatchScore, &'a dyn MatchEquity)>,
+}
+
+impl<'a> CubeContext<'a> {
+    pub fn money(value: u32, owner: CubeOwnership) -> CubeContext<'a> {
+        CubeContext {
+            value,
+            owner,
+            cube_life: DEFAULT_CUBE_LIFE,
+            score: None,
+        }
+    }
+
+    pub fn match_play(
+        value: u32,
+        owner: CubeOwnership,
+        score: MatchScore,
+        met: &'a dyn MatchEquity,
+    ) -> CubeContext<'a> {
+        CubeContext {
+            value,
+            owner,
+            cube_life: DEFAULT_CUBE_LIFE,
+            score: Some((score, met)),
+        }
+    }
+
+    pub fn with_cube_life(self, cube_life: f64) -> CubeContext<'a> {
+        CubeContext { cube_life, ..self }
+    }
+
+    /// Whether the player on roll is allowed to, and has any reason to, double.
+    pub fn can_double(&self) -> bool {
+        if self.owner == CubeOwnership::Opponent {
+            return false;
+        }
+
+        match self.score {
+            // The cube is dead if winning at the current value already wins the match.
+            Some((score, _)) => !score.crawford && score.player_away > self.value,
+            None => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CubeAction {
+    NoDouble,
+    DoubleTake,
+    DoublePass,
+    /// Good enough to cash, but better to play on for a gammon.
+    TooGood,
+}
+
+/// The outcome of a cube decision. Equities are cubeful, from the point of view of the player
+/// on roll, and normalized to the current cube: winning a single game at the current cube value
+/// is worth 1. For money play, multiply by the cube value to get points.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CubeDecision {
+    pub no_double: f64,
+    pub double_take: f64,
+    pub double_pass: f64,
+    pub action: CubeAction,
+    /// The winning chances the opponent needs to take a double, given the gammon rates.
+    pub take_point: f64,
+}
+
+impl CubeDecision {
+    /// Whether the opponent should take if doubled.
+    pub fn is_take(&self) -> bool {
+        self.double_take <= self.double_pass
+    }
+
+    /// The equity after the best cube action by both players.
+    pub fn equity(&self) -> f64 {
+        match self.action {
+            CubeAction::NoDouble | CubeAction::TooGood => self.no_double,
+            CubeAction::DoubleTake => self.double_take,
+            CubeAction::DoublePass => self.double_pass,
+        }
+    }
+}
+
+// The values of all game outcomes at one cube level, normalized so that winning a single game
+// is 1 and losing one is -1. `low` and `high` are the absolute values (points or match winning
+// chances) of those two, so values can be converted back and forth between levels.
+struct Level {
+    win: [f64; 3],
+    lose: [f64; 3],
+    low: f64,
+    high: f64,
+}
+
+impl Level {
+    fn make(cube: u32, context: &CubeContext) -> Level {
+        let absolute: Vec<(f64, f64)> = (1..=3)
+            .map(|k| match context.score {
+                None => (f64::from(k * cube), -f64::from(k * cube)),
+                Some((score, met)) => (
+                    mwc_after(&score, met, k * cube, true),
+                    mwc_after(&score, met, k * cube, false),
+                ),
+            })
+            .collect();
+
+        let (high, low) = absolute[0];
+        let mut level = Level {
+            win: [0.0; 3],
+            lose: [0.0; 3],
+            low,
+            high,
+        };
+
+        for (k, (win, lose)) in absolute.iter().enumerate() {
+            level.win[k] = level.normalize(*win);
+            level.lose[k] = level.normalize(*lose);
+        }
+
+        level
+    }
+
+    fn normalize(&self, value: f64) -> f64 {
+        2.0 * (value - self.low) / (self.high - self.low) - 1.0
+    }
+
+    fn denormalize(&self, equity: f64) -> f64 {
+        self.low + (equity + 1.0) / 2.0 * (self.high - self.low)
+    }
+
+    // The average value of a win and of a loss (as a positive number), given the gammon rates.
+    fn win_loss_values(&self, e: &Evaluation) -> (f64, f64) {
+        let w = if e.win > 0.0 {
+            ((e.win - e.win_gammon) * self.win[0]
+                + (e.win_gammon - e.win_backgammon) * self.win[1]
+                + e.win_backgammon * self.win[2])
+                / e.win
+        } else {
+            1.0
+        };
+
+        let l = if e.lose() > 0.0 {
+            -((e.lose() - e.lose_gammon) * self.lose[0]
+                + (e.lose_gammon - e.lose_backgammon) * self.lose[1]
+                + e.lose_backgammon * self.lose[2])
+                / e.lose()
+        } else {
+            1.0
+        };
+
+        (w, l)
+    }
+}
+
+// Janowski's cubeful equity for winning chances `p`, given the average values of wins and losses,
+// interpolating between a dead cube and a fully live one.
+fn janowski(p: f64, w: f64, l: f64, owner: CubeOwnership, cube_life: f64) -> f64 {
+    let dead = p * (w + l) - l;
+
+    // With a live cube, the opponent's take point and the player's cash point.
+    let take_point = (l - 0.5) / (w + l + 0.5);
+    let cash_point = (l + 1.0) / (w + l + 0.5);
+
+    let line = |p0: f64, e0: f64, p1: f64, e1: f64| e0 + (p - p0) * (e1 - e0) / (p1 - p0);
+
+    let live = match owner {
+        CubeOwnership::Player if p < cash_point => line(0.0, -l, cash_point, 1.0),
+        CubeOwnership::Opponent if p < take_point => line(0.0, -l, take_point, -1.0),
+        CubeOwnership::Opponent => line(take_point, -1.0, 1.0, w),
+        CubeOwnership::Centered if p < take_point => line(0.0, -l, take_point, -1.0),
+        CubeOwnership::Centered if p < cash_point => line(take_point, -1.0, cash_point, 1.0),
+        _ => line(cash_point, 1.0, 1.0, w),
+    };
+
+    cube_life * live + (1.0 - cube_life) * dead
+}
+
+// Whether the owner of a cube of `value` could still use it, in match play.
+fn is_live(context: &CubeContext, owner_is_player: bool, value: u32) -> bool {
+    match context.score {
+        Some((score, _)) => {
+            let away = if owner_is_player {
+                score.player_away
+            } else {
+                score.opponent_away
+            };
+            !score.crawford && away > value
+        }
+        None => true,
+    }
+}
+
+/// Turns cubeless outcome probabilities into cubeful equities for no double, double/take and
+/// double/pass, using Janowski's cube life index model.
+pub fn cube_decision(evaluation: &Evaluation, context: &CubeContext) -> CubeDecision {
+    let level = Level::make(context.value, context);
+    let doubled = Level::make(2 * context.value, context);
+
+    let no_double_life = match context.owner {
+        CubeOwnership::Player => is_live(context, true, context.value),
+        CubeOwnership::Opponent => is_live(context, false, context.value),
+        CubeOwnership::Centered => {
+            is_live(context, true, context.value) || is_live(context, false, context.value)
+        }
+    };
+    let double_take_life = is_live(context, false, 2 * context.value);
+
+    let life = |live: bool| if live { context.cube_life } else { 0.0 };
+
+    let (w, l) = level.win_loss_values(evaluation);
+    let no_double = janowski(evaluation.win, w, l, context.owner, life(no_double_life));
+
+    let (w2, l2) = doubled.win_loss_values(evaluation);
+    let double_take_at = |p: f64| {
+        let e = janowski(p, w2, l2, CubeOwnership::Opponent, life(double_take_life));
+        level.normalize(doubled.denormalize(e))
+    };
+    let double_take = double_take_at(evaluation.win);
+
+    // Paying off a double is always worth a single game at the current cube.
+    let double_pass = 1.0;
+
+    // For fixed gammon rates, the double/take equity grows with the winning chances.
+    let mut low = 0.0;
+    let mut high = 1.0;
+    for _ in 0..50 {
+        let mid = (low + high) / 2.0;
+        if double_take_at(mid) < double_pass {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    let take_point = 1.0 - low;
+
+    CubeDecision {
+        no_double,
+        double_take,
+        double_pass,
+        action: best_action(context, no_double, double_take, double_pass),
+        take_point,
+    }
+}
+
+fn best_action(
+    context: &CubeContext,
+    no_double: f64,
+    double_take: f64,
+    double_pass: f64,
+) -> CubeAction {
+    if !context.can_double() {
+        CubeAction::NoDouble
+    } else if double_take >= double_pass {
+        if no_double > double_pass {
+            CubeAction::TooGood
+        } else {
+            CubeAction::DoublePass
+        }
+    } else if double_take > no_double {
+        CubeAction::DoubleTake
+    } else {
+        CubeAction::NoDouble
+    }
+}
+
+/// Evaluates `position` with `o` on roll at the given ply, then makes the cube decision. For
+/// money play, the evaluator's exact cubeful equities are used when it has them.
+pub fn position_cube_decision<E: Evaluator>(
+    evaluator: &E,
+    position: &Position,
+    ply: u8,
+    context: &CubeContext,
+) -> CubeDecision {
+    let decision = cube_decision(&evaluate_on_roll(evaluator, position, ply), context);
+
+    match context.score {
+        None => match evaluator.money_cube_equities(position, context.owner) {
+            Some((no_double, double_take)) => CubeDecision {
+                no_double,
+                double_take,
+                action: best_action(context, no_double, double_take, decision.double_pass),
+                ..decision
+            },
+            None => decision,
+        },
+        Some(_) => decision,
+    }
+}
+
+/// The chances that `o`, on roll, loses their market by not doubling now: after `o`'s roll and
+/// `x`'s reply, each played by 0-ply evaluation, `o` would be too strong for `x` to take.
+pub fn market_losers<E: Evaluator>(
+    evaluator: &E,
+    position: &Position,
+    context: &CubeContext,
+) -> f64 {
+    let mut losers = 0.0;
+    let rolls = all_rolls();
+
+    for (o_dice, o_weight) in rolls.iter() {
+        let after_o = best_o_play(evaluator, position, o_dice);
+        if after_o.is_over() {
+            continue;
+        }
+
+        for (x_dice, x_weight) in rolls.iter() {
+            let after_x = best_o_play(evaluator, &after_o.flip(), x_dice).flip();
+            if after_x.is_over() {
+                continue;
+            }
+
+            let decision = cube_decision(&evaluator.evaluate(&after_x), context);
+            if !decision.is_take() {
+                losers += o_weight * x_weight;
+            }
+        }
+    }
+
+    losers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    fn no_gammons(p: f64) -> Evaluation {
+        Evaluation::make(p, 0.0, 0.0, 0.0, 0.0)
+    }
+
+    #[test]
+    fn test_money_take_points() {
+        let dead = CubeContext::money(1, CubeOwnership::Centered).with_cube_life(0.0);
+        let live = CubeContext::money(1, CubeOwnership::Centered).with_cube_life(1.0);
+
+        assert!(close(
+            0.25,
+            cube_decision(&no_gammons(0.7), &dead).take_point
+        ));
+        assert!(close(
+            0.2,
+            cube_decision(&no_gammons(0.7), &live).take_point
+        ));
+
+        // Gammons make taking harder.
+        let gammonish = Evaluation::make(0.7, 0.2, 0.0, 0.0, 0.0);
+        assert!(cube_decision(&gammonish, &dead).take_point > 0.25);
+    }
+
+    #[test]
+    fn test_money_decisions() {
+        let cube = CubeContext::money(1, CubeOwnership::Centered).with_cube_life(2.0 / 3.0);
+
+        let d = cube_decision(&no_gammons(0.5), &cube);
+        assert_eq!(CubeAction::NoDouble, d.action);
+
+        let d = cube_decision(&no_gammons(0.78), &cube);
+        assert_eq!(CubeAction::DoubleTake, d.action);
+        assert!(close(0.8089, d.no_double));
+        assert!(close(0.9733, d.double_take));
+        assert!(d.is_take());
+
+        let d = cube_decision(&no_gammons(0.9), &cube);
+        assert_eq!(CubeAction::DoublePass, d.action);
+        assert!(close(1.0, d.equity()));
+
+        let d = cube_decision(&Evaluation::make(0.9, 0.6, 0.0, 0.0, 0.0), &cube);
+        assert_eq!(CubeAction::TooGood, d.action);
+
+        let theirs = CubeContext::money(2, CubeOwnership::Opponent);
+        assert_eq!(
+            CubeAction::NoDouble,
+            cube_decision(&no_gammons(0.78), &theirs).action
+        );
+    }
+
+    // A crude table where each point of lead is worth 5%.
+    struct Linear;
+
+    impl MatchEquity for Linear {
+        fn mwc(&self, player_away: u32, opponent_away: u32, _: bool) -> f64 {
+            let lead = f64::from(opponent_away) - f64::from(player_away);
+            (0.5 + 0.05 * lead).clamp(0.0, 1.0)
+        }
+    }
+
+    #[test]
+    fn test_match_decisions() {
+        let even = no_gammons(0.5);
+
+        let crawford = MatchScore::make(3, 1, true);
+        let cube = CubeContext::match_play(1, CubeOwnership::Centered, crawford, &Linear);
+        assert!(!cube.can_double());
+        assert_eq!(CubeAction::NoDouble, cube_decision(&even, &cube).action);
+
+        // After the Crawford game, the trailer should double right away...
+        let trailer = MatchScore::make(2, 1, false);
+        let cube = CubeContext::match_play(1, CubeOwnership::Centered, trailer, &Linear);
+        assert_ne!(CubeAction::NoDouble, cube_decision(&even, &cube).action);
+
+        // ...and the leader never has a reason to.
+        let leader = trailer.invert();
+        let cube = CubeContext::match_play(1, CubeOwnership::Centered, leader, &Linear);
+        assert!(!cube.can_double());
+    }
+}
@@ -5,7 +5,7 @@ use std::fmt;
 // const DICE_CHARS: [char; 6] = ['⚀', '⚁', '⚂', '⚃', '⚄', '⚅'];
 const DICE_CHARS: [char; 6] = ['1', '2', '3', '4', '5', '6'];
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Dice {
     first: u8,
     second: u8,
@@ -61,6 +61,18 @@ impl fmt::Display for Dice {
 
 impl Eq for Dice {}
 
+/// The 21 distinct rolls, each with its probability.
+pub fn all_rolls() -> Vec<(Dice, f64)> {
+    let mut rolls = Vec::new();
+    for high in 1..=6 {
+        for low in 1..=high {
+            let weight = if high == low { 1.0 / 36.0 } else { 2.0 / 36.0 };
+            rolls.push((Dice::make(high, low), weight));
+        }
+    }
+    rolls
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -68,6 +80,7 @@ mod tests {
     const TEST_ROLLS: u32 = 1_000_000;
 
     #[test]
+    #[allow(clippy::manual_range_contains, clippy::unused_unit)]
     fn test_dist_1() -> () {
         let mut d1_counts: [u32; 6] = [0; 6];
         let mut d2_counts: [u32; 6] = [0; 6];
@@ -0,0 +1,182 @@
+use super::cache::CacheKey;
+use super::position::Position;
+use super::rollout::rollout_o;
+
+/// Cubeless outcome probabilities, from the point of view of the player on roll.
+///
+/// As everywhere else, the player on roll is assumed to be `o`. Gammon and backgammon
+/// probabilities are cumulative: a backgammon also counts as a gammon and as a win.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Evaluation {
+    pub win: f64,
+    pub win_gammon: f64,
+    pub win_backgammon: f64,
+    pub lose_gammon: f64,
+    pub lose_backgammon: f64,
+}
+
+impl Evaluation {
+    pub fn make(
+        win: f64,
+        win_gammon: f64,
+        win_backgammon: f64,
+        lose_gammon: f64,
+        lose_backgammon: f64,
+    ) -> Evaluation {
+        Evaluation {
+            win,
+            win_gammon,
+            win_backgammon,
+            lose_gammon,
+            lose_backgammon,
+        }
+    }
+
+    /// The exact evaluation of a finished game, if `position` is one.
+    pub fn terminal(position: &Position) -> Option<Evaluation> {
+        if position.o_has_won() {
+            let gammon = if position.o_has_gammoned() { 1.0 } else { 0.0 };
+            let backgammon = if position.o_has_backgammoned() {
+                1.0
+            } else {
+                0.0
+            };
+            Some(Evaluation::make(1.0, gammon, backgammon, 0.0, 0.0))
+        } else if position.x_has_won() {
+            let gammon = if position.x_has_gammoned() { 1.0 } else { 0.0 };
+            let backgammon = if position.x_has_backgammoned() {
+                1.0
+            } else {
+                0.0
+            };
+            Some(Evaluation::make(0.0, 0.0, 0.0, gammon, backgammon))
+        } else {
+            None
+        }
+    }
+
+    pub fn lose(&self) -> f64 {
+        1.0 - self.win
+    }
+
+    /// Cubeless money equity, in points per game.
+    pub fn equity(&self) -> f64 {
+        self.win - self.lose() + self.win_gammon - self.lose_gammon + self.win_backgammon
+            - self.lose_backgammon
+    }
+
+    /// The same probabilities, seen from the opponent's side.
+    pub fn invert(&self) -> Evaluation {
+        Evaluation {
+            win: self.lose(),
+            win_gammon: self.lose_gammon,
+            win_backgammon: self.lose_backgammon,
+            lose_gammon: self.win_gammon,
+            lose_backgammon: self.win_backgammon,
+        }
+    }
+}
+
+/// Who holds the cube, relative to the player on roll.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CubeOwnership {
+    Centered,
+    Player,
+    Opponent,
+}
+
+impl CubeOwnership {
+    /// The same ownership, seen from the opponent's side.
+    pub fn invert(&self) -> CubeOwnership {
+        match self {
+            CubeOwnership::Centered => CubeOwnership::Centered,
+            CubeOwnership::Player => CubeOwnership::Opponent,
+            CubeOwnership::Opponent => CubeOwnership::Player,
+        }
+    }
+}
+
+/// Anything that can estimate outcome probabilities for a position with `o` on roll.
+pub trait Evaluator {
+    fn evaluate(&self, position: &Position) -> Evaluation;
+
+    /// A previously stored evaluation, if this evaluator remembers any. Used by `search` to
+    /// avoid recomputing deeper evaluations.
+    fn cached(&self, _key: &CacheKey) -> Option<Evaluation> {
+        None
+    }
+
+    fn store(&self, _key: CacheKey, _evaluation: Evaluation) {}
+
+    /// Exact money equities for `o`, on roll with the given cube, if this evaluator knows them:
+    /// after no double, and after double/take, normalized to a cube value of 1. Cube decisions
+    /// use them instead of estimating them from the outcome probabilities.
+    fn money_cube_equities(
+        &self,
+        _position: &Position,
+        _cube: CubeOwnership,
+    ) -> Option<(f64, f64)> {
+        None
+    }
+}
+
+impl<E: Evaluator + ?Sized> Evaluator for &E {
+    fn evaluate(&self, position: &Position) -> Evaluation {
+        (**self).evaluate(position)
+    }
+
+    fn cached(&self, key: &CacheKey) -> Option<Evaluation> {
+        (**self).cached(key)
+    }
+
+    fn store(&self, key: CacheKey, evaluation: Evaluation) {
+        (**self).store(key, evaluation)
+    }
+
+    fn money_cube_equities(&self, position: &Position, cube: CubeOwnership) -> Option<(f64, f64)> {
+        (**self).money_cube_equities(position, cube)
+    }
+}
+
+/// Evaluates positions by playing them out with random moves. Slow, and only a rough guide.
+pub struct RolloutEvaluator;
+
+impl Evaluator for RolloutEvaluator {
+    fn evaluate(&self, position: &Position) -> Evaluation {
+        if let Some(evaluation) = Evaluation::terminal(position) {
+            return evaluation;
+        }
+
+        let stats = rollout_o(position);
+        Evaluation::make(
+            stats.o_win,
+            stats.o_gammon,
+            stats.o_backgammon,
+            stats.x_gammon,
+            stats.x_backgammon,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_equity_and_invert() {
+        let e = Evaluation::make(0.6, 0.2, 0.05, 0.1, 0.01);
+
+        assert!((e.equity() - (0.6 - 0.4 + 0.2 - 0.1 + 0.05 - 0.01)).abs() < 1e-9);
+        assert!((e.invert().equity() + e.equity()).abs() < 1e-9);
+        assert_eq!(e, e.invert().invert());
+    }
+
+    #[test]
+    fn test_terminal() {
+        let p = Position::make(&[], &[(1, 2), (2, 13)], 0, 0, 15, 0);
+        let e = Evaluation::terminal(&p).unwrap();
+
+        assert_eq!(Evaluation::make(1.0, 1.0, 1.0, 0.0, 0.0), e);
+        assert!(Evaluation::terminal(&Position::initial()).is_none());
+    }
+}
@@ -1,24 +1,407 @@
+use std::error::Error;
+use std::fmt;
+
 use super::moves::Move;
+use super::position::{Position, Side};
+
+/// How many points a player gives up by resigning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resignation {
+    Single,
+    Gammon,
+    Backgammon,
+}
+
+impl Resignation {
+    /// The resignation worth the given points, before the cube.
+    pub fn from_points(points: u32) -> Option<Resignation> {
+        match points {
+            1 => Some(Resignation::Single),
+            2 => Some(Resignation::Gammon),
+            3 => Some(Resignation::Backgammon),
+            _ => None,
+        }
+    }
+
+    pub fn points(&self) -> u32 {
+        match self {
+            Resignation::Single => 1,
+            Resignation::Gammon => 2,
+            Resignation::Backgammon => 3,
+        }
+    }
+}
+
+/// Anything a player can do in their half of a turn.
+#[derive(Debug, PartialEq)]
+pub enum Action {
+    Move(Move),
+    /// Offers the cube at the given value.
+    Double(u32),
+    Take,
+    Drop,
+    /// Takes, and immediately redoubles to the given value while keeping the cube.
+    Beaver(u32),
+    Resign(Resignation),
+    /// Accepts the opponent's resignation, which ends the game.
+    Accept,
+    /// Rejects the opponent's resignation, and play goes on.
+    Reject,
+}
 
-#[derive(Debug)]
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::Move(m) => write!(f, "{}", m),
+            Action::Double(value) => write!(f, "Doubles => {}", value),
+            Action::Take => write!(f, "Takes"),
+            Action::Drop => write!(f, "Drops"),
+            Action::Beaver(value) => write!(f, "Beavers => {}", value),
+            Action::Resign(r) => write!(f, "Resigns {}", r.points()),
+            Action::Accept => write!(f, "Accepts"),
+            Action::Reject => write!(f, "Rejects"),
+        }
+    }
+}
+
+/// The state of the doubling cube.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cube {
+    pub value: u32,
+    /// `None` while the cube is centered.
+    pub owner: Option<Side>,
+    /// The value offered by a double that hasn't been answered yet.
+    pub offered: Option<u32>,
+}
+
+impl Cube {
+    pub fn initial() -> Cube {
+        Cube {
+            value: 1,
+            owner: None,
+            offered: None,
+        }
+    }
+
+    /// Updates the cube after `side` took `action`.
+    pub fn apply(&mut self, side: Side, action: &Action) {
+        match action {
+            Action::Double(value) => self.offered = Some(*value),
+            Action::Take => {
+                self.value = self.offered.take().unwrap_or(2 * self.value);
+                self.owner = Some(side);
+            }
+            Action::Beaver(value) => {
+                self.value = *value;
+                self.owner = Some(side);
+                self.offered = None;
+            }
+            Action::Drop => self.offered = None,
+            Action::Move(_) | Action::Resign(_) | Action::Accept | Action::Reject => (),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
 pub struct Game {
-    /** The game turns, with `o` move then `x` move. */
-    pub turns: Vec<(Option<Move>, Option<Move>)>,
+    /// The game's index in its match, starting at 1.
+    pub number: u32,
+    pub o_player: String,
+    pub x_player: String,
+    /// The scores at the start of the game.
+    pub o_score: u32,
+    pub x_score: u32,
+    /// The player who won the opening roll, and played first.
+    pub starting_side: Option<Side>,
+    /** The game turns, with `o` action then `x` action. If `x` started, the first turn has no
+     * `o` action. */
+    pub turns: Vec<(Option<Action>, Option<Action>)>,
+    pub winner: Option<Side>,
+    /// The points won by the winner, cube and gammons included.
+    pub points: u32,
+    /// Whether this was the Crawford game, in which the cube cannot be used.
+    pub crawford: bool,
 }
 
 impl Game {
-    pub fn make(turns: Vec<(Option<Move>, Option<Move>)>) -> Game {
-        Game { turns }
+    /// A game with the given starting side and turns, and no other information.
+    pub fn make(starting_side: Option<Side>, turns: Vec<(Option<Action>, Option<Action>)>) -> Game {
+        Game {
+            number: 1,
+            o_player: String::new(),
+            x_player: String::new(),
+            o_score: 0,
+            x_score: 0,
+            starting_side,
+            turns,
+            winner: None,
+            points: 0,
+            crawford: false,
+        }
+    }
+}
+
+impl Game {
+    /// Replays the game from the initial position, one half-turn at a time.
+    pub fn replay(&self) -> Replay<'_> {
+        Replay {
+            turns: &self.turns,
+            turn: 0,
+            side: self.starting_side.unwrap_or(Side::O),
+            position: Position::initial(),
+            cube: Cube::initial(),
+            failed: false,
+        }
+    }
+}
+
+/// One player's action, with the positions around it.
+#[derive(Debug, PartialEq)]
+pub struct ReplayStep<'a> {
+    /// The turn number, as written in the game record, starting at 1.
+    pub turn: usize,
+    pub side: Side,
+    pub action: &'a Action,
+    pub before: Position,
+    pub after: Position,
+    /// The cube after the action.
+    pub cube: Cube,
+}
+
+/// A move that couldn't be played during a replay.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayError {
+    pub turn: usize,
+    pub side: Side,
+    pub action: String,
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "turn {}: {:?} cannot play {}",
+            self.turn, self.side, self.action
+        )
+    }
+}
+
+impl Error for ReplayError {}
+
+/// Iterator over the steps of a game, see `Game::replay`. It stops after the first error.
+pub struct Replay<'a> {
+    turns: &'a [(Option<Action>, Option<Action>)],
+    turn: usize,
+    side: Side,
+    position: Position,
+    cube: Cube,
+    failed: bool,
+}
+
+impl<'a> Iterator for Replay<'a> {
+    type Item = Result<ReplayStep<'a>, ReplayError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.failed && self.turn < self.turns.len() {
+            let turn = self.turn;
+            let side = self.side;
+            let action = match side {
+                Side::O => &self.turns[turn].0,
+                Side::X => {
+                    self.turn += 1;
+                    &self.turns[turn].1
+                }
+            };
+            self.side = side.opponent();
+
+            let action = match action {
+                Some(action) => action,
+                None => continue,
+            };
+
+            let before = self.position;
+            if let Action::Move(m) = action {
+                let after = match side {
+                    Side::O => before.try_apply_o_move(m),
+                    Side::X => before.try_apply_x_move(m),
+                };
+
+                match after {
+                    Some(after) => self.position = after,
+                    None => {
+                        self.failed = true;
+                        return Some(Err(ReplayError {
+                            turn: turn + 1,
+                            side,
+                            action: action.to_string(),
+                        }));
+                    }
+                }
+            }
+            self.cube.apply(side, action);
+
+            return Some(Ok(ReplayStep {
+                turn: turn + 1,
+                side,
+                action,
+                before,
+                after: self.position,
+                cube: self.cube,
+            }));
+        }
+
+        None
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct Match {
+    /// The number of points needed to win the match, or 0 for a money session.
+    pub length: u32,
     pub games: Vec<Game>,
+    /// Key/value headers such as `Site` or `EventDate`, in the order they were given.
+    pub metadata: Vec<(String, String)>,
 }
 
 impl Match {
-    pub fn make(games: Vec<Game>) -> Match {
-        Match { games }
+    /// A match of the given length. The Crawford game, if any, is determined from the scores.
+    pub fn make(length: u32, mut games: Vec<Game>) -> Match {
+        if length > 1 {
+            let crawford = games
+                .iter_mut()
+                .find(|g| (g.o_score + 1 == length) != (g.x_score + 1 == length));
+
+            if let Some(g) = crawford {
+                g.crawford = true;
+            }
+        }
+
+        Match {
+            length,
+            games,
+            metadata: vec![],
+        }
+    }
+
+    /// The first value given for a metadata key.
+    pub fn metadata(&self, key: &str) -> Option<&str> {
+        self.metadata
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// The side that won the match, if it was played to the end.
+    pub fn winner(&self) -> Option<Side> {
+        let last = self.games.last()?;
+        let winner = last.winner?;
+
+        let score = match winner {
+            Side::O => last.o_score,
+            Side::X => last.x_score,
+        };
+
+        if self.length > 0 && score + last.points >= self.length {
+            Some(winner)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cube_tracking() {
+        let mut cube = Cube::initial();
+
+        cube.apply(Side::X, &Action::Double(2));
+        assert_eq!(Some(2), cube.offered);
+        assert_eq!(1, cube.value);
+
+        cube.apply(Side::O, &Action::Take);
+        assert_eq!(
+            Cube {
+                value: 2,
+                owner: Some(Side::O),
+                offered: None
+            },
+            cube
+        );
+
+        cube.apply(Side::O, &Action::Double(4));
+        cube.apply(Side::X, &Action::Beaver(8));
+        assert_eq!(
+            Cube {
+                value: 8,
+                owner: Some(Side::X),
+                offered: None
+            },
+            cube
+        );
+    }
+
+    #[test]
+    fn test_crawford_game() {
+        let game = |o_score, x_score| {
+            let mut g = Game::make(None, vec![]);
+            g.o_score = o_score;
+            g.x_score = x_score;
+            g
+        };
+
+        let m = Match::make(5, vec![game(0, 0), game(4, 2), game(4, 3), game(4, 4)]);
+        let crawford: Vec<bool> = m.games.iter().map(|g| g.crawford).collect();
+        assert_eq!(vec![false, true, false, false], crawford);
+    }
+
+    #[test]
+    fn test_replay() {
+        use crate::dice::Dice;
+        use crate::moves::CheckerMove;
+
+        let play = |d1, d2, moves: &[(u8, u8)]| {
+            let moves = moves
+                .iter()
+                .map(|&(from, to)| CheckerMove::make(from, to, false))
+                .collect();
+            Some(Action::Move(Move::make(Dice::make(d1, d2), moves)))
+        };
+
+        let game = Game::make(
+            Some(Side::X),
+            vec![
+                (None, play(3, 1, &[(8, 5), (6, 5)])),
+                (play(6, 4, &[(24, 18), (13, 9)]), Some(Action::Double(2))),
+                (Some(Action::Take), play(2, 1, &[(13, 11), (6, 5)])),
+                (play(5, 5, &[(9, 4), (9, 4)]), None),
+            ],
+        );
+
+        let steps: Vec<_> = game.replay().collect();
+        assert_eq!(6, steps.len());
+
+        let first = steps[0].as_ref().unwrap();
+        assert_eq!((1, Side::X), (first.turn, first.side));
+        assert_eq!(Position::initial(), first.before);
+
+        let second = steps[1].as_ref().unwrap();
+        assert_eq!(first.after, second.before);
+
+        let take = steps[3].as_ref().unwrap();
+        assert_eq!(take.before, take.after);
+        assert_eq!(2, take.cube.value);
+
+        // There is only one checker on the 9 point.
+        assert_eq!(
+            &Err(ReplayError {
+                turn: 4,
+                side: Side::O,
+                action: "5-5: 9/4 9/4".to_string()
+            }),
+            steps.last().unwrap()
+        );
     }
 }
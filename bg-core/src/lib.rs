@@ -1,7 +1,17 @@
+pub mod bearoff;
+pub mod cache;
+pub mod cube;
 pub mod dice;
+pub mod eval;
 pub mod game;
+pub mod met;
 pub mod movegen;
 pub mod moves;
+pub mod policy;
 pub mod position;
 // FIXME this could be its own crate/package
 pub mod rollout;
+pub mod search;
+pub mod state;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
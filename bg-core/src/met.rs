@@ -0,0 +1,376 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::eval::Evaluation;
+
+/// The away scores at the start of a game, from the point of view of the player on roll.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MatchScore {
+    pub player_away: u32,
+    pub opponent_away: u32,
+    /// Whether this game is the Crawford game, in which the cube cannot be used.
+    pub crawford: bool,
+}
+
+impl MatchScore {
+    pub fn make(player_away: u32, opponent_away: u32, crawford: bool) -> MatchScore {
+        MatchScore {
+            player_away,
+            opponent_away,
+            crawford,
+        }
+    }
+
+    /// Whether the Crawford game has already been played.
+    pub fn is_post_crawford(&self) -> bool {
+        !self.crawford && (self.player_away == 1 || self.opponent_away == 1)
+    }
+
+    /// The same score, seen from the opponent's side.
+    pub fn invert(&self) -> MatchScore {
+        MatchScore::make(self.opponent_away, self.player_away, self.crawford)
+    }
+}
+
+/// A source of match winning chances, e.g. a match equity table.
+pub trait MatchEquity {
+    /// The chances that the player needing `player_away` points wins the match, at the start of
+    /// a game. With a player 1-away, `post_crawford` tells whether the Crawford game was played.
+    fn mwc(&self, player_away: u32, opponent_away: u32, post_crawford: bool) -> f64;
+}
+
+/// The match winning chances of the player on roll after they win or lose `points` in the game
+/// played at `score`.
+pub fn mwc_after(score: &MatchScore, met: &dyn MatchEquity, points: u32, win: bool) -> f64 {
+    let (player_away, opponent_away) = if win {
+        (
+            score.player_away.saturating_sub(points),
+            score.opponent_away,
+        )
+    } else {
+        (
+            score.player_away,
+            score.opponent_away.saturating_sub(points),
+        )
+    };
+
+    if player_away == 0 {
+        return 1.0;
+    }
+
+    if opponent_away == 0 {
+        return 0.0;
+    }
+
+    // The game after the Crawford game, or after any post-Crawford game, is post-Crawford.
+    let post_crawford = score.crawford || score.is_post_crawford();
+    met.mwc(player_away, opponent_away, post_crawford)
+}
+
+/// Converts cubeless outcome probabilities into match winning chances for the player on roll,
+/// for a game played at `score` with the cube at `cube`.
+pub fn match_winning_chances(
+    evaluation: &Evaluation,
+    score: &MatchScore,
+    cube: u32,
+    met: &dyn MatchEquity,
+) -> f64 {
+    let e = evaluation;
+    let mwc = |points: u32, win: bool| mwc_after(score, met, points * cube, win);
+
+    (e.win - e.win_gammon) * mwc(1, true)
+        + (e.win_gammon - e.win_backgammon) * mwc(2, true)
+        + e.win_backgammon * mwc(3, true)
+        + (e.lose() - e.lose_gammon) * mwc(1, false)
+        + (e.lose_gammon - e.lose_backgammon) * mwc(2, false)
+        + e.lose_backgammon * mwc(3, false)
+}
+
+// Parameters of the built-in table: the share of games ending in a gammon, the same after the
+// Crawford game (when the trailer has nothing to lose), and how often the cube ends up at 2.
+const GAMMON_RATE: f64 = 0.2;
+const POST_CRAWFORD_GAMMON_RATE: f64 = 0.25;
+const DOUBLED_RATE: f64 = 0.6;
+
+/// A match equity table.
+///
+/// `pre_crawford[i][j]` is the match winning chances of a player `i + 1` away against one `j + 1`
+/// away, including the Crawford game when either is 1-away. `post_crawford[i]` is the chances of
+/// a trailer `i + 1` away after the Crawford game. Scores beyond the table are clamped to its size.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchEquityTable {
+    pub name: String,
+    pre_crawford: Vec<Vec<f64>>,
+    post_crawford: Vec<f64>,
+}
+
+impl MatchEquityTable {
+    pub fn make(
+        name: &str,
+        pre_crawford: Vec<Vec<f64>>,
+        post_crawford: Vec<f64>,
+    ) -> MatchEquityTable {
+        assert!(!pre_crawford.is_empty());
+        assert!(pre_crawford
+            .iter()
+            .all(|row| row.len() == pre_crawford.len()));
+        assert!(post_crawford.len() >= pre_crawford.len());
+
+        MatchEquityTable {
+            name: name.to_string(),
+            pre_crawford,
+            post_crawford,
+        }
+    }
+
+    /// The built-in table, for matches up to 25 points.
+    ///
+    /// It is an approximation, not a published table: each game is won half the time, a fixed
+    /// share of wins are gammons, and the cube is assumed to reach 2 in a fixed share of
+    /// pre-Crawford games. After the Crawford game, the trailer doubles immediately and the
+    /// leader drops when that's free. For analysis, `load` a published table such as GNU
+    /// Backgammon's `met/Kazaross-XG2.xml` instead.
+    pub fn built_in() -> MatchEquityTable {
+        MatchEquityTable::computed(25)
+    }
+
+    fn computed(size: usize) -> MatchEquityTable {
+        // The trailer's chances after the Crawford game.
+        let mut post_crawford: Vec<f64> = vec![0.5];
+        for n in 2..=size {
+            let t = |k: usize| if k < 1 { 1.0 } else { post_crawford[k - 1] };
+            let take = 0.5 * (1.0 - POST_CRAWFORD_GAMMON_RATE) * t(n.saturating_sub(2))
+                + 0.5 * POST_CRAWFORD_GAMMON_RATE * t(n.saturating_sub(4));
+            let value = if n % 2 == 0 { take.min(t(n - 1)) } else { take };
+            post_crawford.push(value);
+        }
+
+        let mut table = vec![vec![0.0; size]; size];
+        for a in 1..=size {
+            for b in 1..=size {
+                let m = |a: isize, b: isize| -> f64 {
+                    if a < 1 {
+                        1.0
+                    } else if b < 1 {
+                        0.0
+                    } else {
+                        table[a as usize - 1][b as usize - 1]
+                    }
+                };
+                let t = |k: usize| if k < 1 { 1.0 } else { post_crawford[k - 1] };
+                let crawford_trailer = |n: usize| {
+                    0.5 * (1.0 - GAMMON_RATE) * t(n - 1)
+                        + 0.5 * GAMMON_RATE * t(n.saturating_sub(2))
+                };
+
+                let value = if a == 1 && b == 1 {
+                    0.5
+                } else if a == 1 {
+                    1.0 - crawford_trailer(b)
+                } else if b == 1 {
+                    crawford_trailer(a)
+                } else {
+                    let (a, b) = (a as isize, b as isize);
+                    [(1, 1.0 - DOUBLED_RATE), (2, DOUBLED_RATE)]
+                        .iter()
+                        .map(|&(c, weight)| {
+                            weight
+                                * (0.5 * (1.0 - GAMMON_RATE) * (m(a - c, b) + m(a, b - c))
+                                    + 0.5 * GAMMON_RATE * (m(a - 2 * c, b) + m(a, b - 2 * c)))
+                        })
+                        .sum()
+                };
+
+                table[a - 1][b - 1] = value;
+            }
+        }
+
+        MatchEquityTable::make("Built-in", table, post_crawford)
+    }
+
+    /// The largest match length the table covers.
+    pub fn len(&self) -> usize {
+        self.pre_crawford.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pre_crawford.is_empty()
+    }
+
+    /// Reads a table in GNU Backgammon's XML format. Only explicit tables are supported, and the
+    /// post-Crawford row is read as the trailer's chances, as GNU Backgammon writes them.
+    pub fn parse_gnubg_xml(text: &str) -> io::Result<MatchEquityTable> {
+        let name = between(text, "<name>", "</name>")
+            .map(|(n, _)| n.trim().to_string())
+            .unwrap_or_else(|| "Unnamed".to_string());
+
+        let pre = section(text, "<pre-crawford-table", "</pre-crawford-table>")
+            .ok_or_else(|| invalid_data("missing pre-Crawford table"))?;
+        let post = section(text, "<post-crawford-table", "</post-crawford-table>")
+            .ok_or_else(|| invalid_data("missing post-Crawford table"))?;
+
+        let pre_crawford = rows(pre)?;
+        let post_crawford = rows(post)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| invalid_data("empty post-Crawford table"))?;
+
+        if pre_crawford.is_empty() || pre_crawford.iter().any(|r| r.len() != pre_crawford.len()) {
+            return Err(invalid_data("pre-Crawford table is not square"));
+        }
+
+        if post_crawford.len() < pre_crawford.len() {
+            return Err(invalid_data("post-Crawford table is too short"));
+        }
+
+        Ok(MatchEquityTable::make(&name, pre_crawford, post_crawford))
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<MatchEquityTable> {
+        MatchEquityTable::parse_gnubg_xml(&fs::read_to_string(path)?)
+    }
+}
+
+impl MatchEquity for MatchEquityTable {
+    fn mwc(&self, player_away: u32, opponent_away: u32, post_crawford: bool) -> f64 {
+        if player_away == 0 {
+            return 1.0;
+        }
+
+        if opponent_away == 0 {
+            return 0.0;
+        }
+
+        let clamp = |away: u32| (away as usize).min(self.len()) - 1;
+
+        if post_crawford && player_away == 1 && opponent_away > 1 {
+            1.0 - self.post_crawford[clamp(opponent_away)]
+        } else if post_crawford && opponent_away == 1 && player_away > 1 {
+            self.post_crawford[clamp(player_away)]
+        } else {
+            self.pre_crawford[clamp(player_away)][clamp(opponent_away)]
+        }
+    }
+}
+
+// The text between `open` and `close`, and what follows it.
+fn between<'a>(text: &'a str, open: &str, close: &str) -> Option<(&'a str, &'a str)> {
+    let start = text.find(open)? + open.len();
+    let end = start + text[start..].find(close)?;
+    Some((&text[start..end], &text[end + close.len()..]))
+}
+
+// The contents of an element whose opening tag may have attributes.
+fn section<'a>(text: &'a str, open: &str, close: &str) -> Option<&'a str> {
+    let start = text.find(open)?;
+    let body = start + text[start..].find('>')? + 1;
+    let end = body + text[body..].find(close)?;
+    Some(&text[body..end])
+}
+
+fn rows(text: &str) -> io::Result<Vec<Vec<f64>>> {
+    let mut result = Vec::new();
+    let mut rest = text;
+
+    while let Some((row, after)) = between(rest, "<row>", "</row>") {
+        let mut values = Vec::new();
+        let mut cells = row;
+
+        while let Some((cell, after_cell)) = between(cells, "<me>", "</me>") {
+            let value: f64 = cell
+                .trim()
+                .parse()
+                .map_err(|_| invalid_data("invalid match equity"))?;
+            values.push(value);
+            cells = after_cell;
+        }
+
+        result.push(values);
+        rest = after;
+    }
+
+    Ok(result)
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_built_in_table() {
+        let met = MatchEquityTable::built_in();
+        assert_eq!(25, met.len());
+
+        for a in 1..=25 {
+            assert!((met.mwc(a, a, false) - 0.5).abs() < 1e-9);
+
+            for b in 1..=25 {
+                // Symmetric, and better when closer to winning.
+                assert!((met.mwc(a, b, false) + met.mwc(b, a, false) - 1.0).abs() < 1e-9);
+                if a > 1 {
+                    assert!(met.mwc(a - 1, b, false) > met.mwc(a, b, false));
+                }
+            }
+        }
+
+        // Roughly in line with published tables.
+        assert!((met.mwc(1, 2, false) - 0.70).abs() < 0.03);
+        assert!((met.mwc(2, 4, false) - 0.67).abs() < 0.05);
+        assert!((met.mwc(3, 1, true) - 0.32).abs() < 0.02);
+    }
+
+    #[test]
+    fn test_match_winning_chances() {
+        let met = MatchEquityTable::built_in();
+        let score = MatchScore::make(3, 3, false);
+
+        let sure_win = Evaluation::make(1.0, 0.0, 0.0, 0.0, 0.0);
+        let sure_gammon = Evaluation::make(1.0, 1.0, 0.0, 0.0, 0.0);
+        let even = Evaluation::make(0.5, 0.0, 0.0, 0.0, 0.0);
+
+        assert!(
+            (match_winning_chances(&sure_win, &score, 1, &met) - met.mwc(2, 3, false)).abs() < 1e-9
+        );
+        assert!((match_winning_chances(&sure_gammon, &score, 2, &met) - 1.0).abs() < 1e-9);
+        assert!((match_winning_chances(&even, &score, 1, &met) - 0.5).abs() < 1e-9);
+
+        // Winning one point in the Crawford game leads to a post-Crawford score.
+        let crawford = MatchScore::make(3, 1, true);
+        assert!(
+            (match_winning_chances(&sure_win, &crawford, 1, &met) - met.mwc(2, 1, true)).abs()
+                < 1e-9
+        );
+    }
+
+    #[test]
+    fn test_parse_gnubg_xml() {
+        let xml = r#"<?xml version = "1.0" encoding = "ISO-8859-1"?>
+<match-equity-table>
+  <info>
+    <name>Tiny</name>
+    <length>2</length>
+  </info>
+  <pre-crawford-table type="explicit">
+    <row> <me>0.5</me> <me>0.7</me> </row>
+    <row> <me>0.3</me> <me>0.5</me> </row>
+  </pre-crawford-table>
+  <post-crawford-table player="both" type="explicit">
+    <row> <me>0.5</me> <me>0.48</me> </row>
+  </post-crawford-table>
+</match-equity-table>"#;
+
+        let met = MatchEquityTable::parse_gnubg_xml(xml).unwrap();
+        assert_eq!("Tiny", met.name);
+        assert_eq!(2, met.len());
+        assert!((met.mwc(1, 2, false) - 0.7).abs() < 1e-9);
+        assert!((met.mwc(2, 1, true) - 0.48).abs() < 1e-9);
+        assert!((met.mwc(1, 2, true) - 0.52).abs() < 1e-9);
+
+        assert!(MatchEquityTable::parse_gnubg_xml("<match-equity-table/>").is_err());
+    }
+}
@@ -2,8 +2,11 @@ use std::cmp::Ordering;
 use std::collections::HashSet;
 
 use super::dice::Dice;
+use super::moves::{CheckerMove, Move};
 use super::position::Position;
 
+// Predates the lint.
+#[allow(clippy::map_flatten)]
 pub fn generate_o_moves(position: &Position, dice: &Dice) -> HashSet<Position> {
     if dice.is_double() {
         let one_move: HashSet<Position> = generate_one_die_moves(position, dice.high())
@@ -88,6 +91,85 @@ pub fn generate_x_moves(position: &Position, dice: &Dice) -> HashSet<Position> {
         .collect()
 }
 
+/// The checker moves that take `o` from `from` to `to` with the given roll, if `to` is one of
+/// the legal plays.
+pub fn find_o_move(from: &Position, dice: &Dice, to: &Position) -> Option<Move> {
+    let legal = generate_o_moves(from, dice);
+    if legal.is_empty() && from == to {
+        return Some(Move::make(*dice, vec![]));
+    }
+    if !legal.contains(to) {
+        return None;
+    }
+
+    let orders: Vec<Vec<u8>> = if dice.is_double() {
+        (1..=4).rev().map(|n| vec![dice.high(); n]).collect()
+    } else {
+        vec![
+            vec![dice.high(), dice.low()],
+            vec![dice.low(), dice.high()],
+            vec![dice.high()],
+            vec![dice.low()],
+        ]
+    };
+
+    orders.iter().find_map(|dies| {
+        let mut steps = Vec::new();
+        if find_steps(from, dies, to, &mut steps) {
+            let checker_moves = steps
+                .iter()
+                .map(|&(src, dst, hits)| CheckerMove::make(src, dst, hits))
+                .collect();
+            Some(Move::make(*dice, checker_moves))
+        } else {
+            None
+        }
+    })
+}
+
+/// Like `find_o_move`, for `x`. Checker moves are numbered from `x`'s point of view.
+pub fn find_x_move(from: &Position, dice: &Dice, to: &Position) -> Option<Move> {
+    find_o_move(&from.flip(), dice, &to.flip())
+}
+
+// Depth-first search for single checker moves, using `dies` in order, that end up in `to`.
+// Steps are (from, to, hits), in the 1-based numbering used by `CheckerMove`.
+fn find_steps(
+    position: &Position,
+    dies: &[u8],
+    to: &Position,
+    steps: &mut Vec<(u8, u8, bool)>,
+) -> bool {
+    let die = match dies.first() {
+        Some(die) => *die,
+        None => return position == to,
+    };
+
+    let next_positions = generate_one_die_moves(position, die);
+
+    for src in (1..=25u8).rev() {
+        let dst = src.saturating_sub(die);
+        let hits = dst > 0 && position.point_x_value(dst - 1) == 1;
+        let step = Move::make(
+            Dice::make(die, die),
+            vec![CheckerMove::make(src, dst, hits)],
+        );
+
+        let next = match position.try_apply_o_move(&step) {
+            Some(next) if next_positions.contains(&next) => next,
+            _ => continue,
+        };
+
+        steps.push((src, dst, hits));
+        if find_steps(&next, &dies[1..], to, steps) {
+            return true;
+        }
+        steps.pop();
+    }
+
+    false
+}
+
 fn generate_one_die_moves(position: &Position, die: u8) -> Vec<Position> {
     let mut results: Vec<Position> = Vec::new();
 
@@ -149,6 +231,24 @@ mod tests {
     use super::*;
 
     #[test]
+    fn test_find_o_move() {
+        let start = Position::initial();
+        let dice = Dice::make(3, 1);
+        let to = start.with_o_move(7, 4).with_o_move(5, 4);
+
+        let m = find_o_move(&start, &dice, &to).unwrap();
+        assert_eq!(Some(to), start.try_apply_o_move(&m));
+        assert_eq!(2, m.moves().len());
+
+        assert!(find_o_move(&start, &dice, &start).is_none());
+        assert_eq!(
+            Some(to.flip()),
+            find_x_move(&start, &dice, &to.flip()).and_then(|m| start.try_apply_x_move(&m))
+        );
+    }
+
+    #[test]
+    #[allow(clippy::unused_unit)]
     fn test_play_both() -> () {
         // Example from https://www.bkgm.com/faq/BasicRules.html#moving_the_checkers
         let x_checkers = [(2, 2), (3, 2), (9, 2), (19, 3), (20, 2), (22, 2), (23, 2)];
@@ -173,6 +273,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::unused_unit)]
     fn test_movegen_play_highest() -> () {
         // Example from https://www.bkgm.com/faq/BasicRules.html#moving_the_checkers
         let x_checkers = [
@@ -213,6 +314,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::unused_unit)]
     fn test_movegen_entering() -> () {
         let start = Position::make(
             &[(6, 5), (8, 3), (13, 5), (24, 1)],
@@ -255,6 +357,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::unused_unit)]
     fn test_movegen_bearing_off_1() -> () {
         // Example from https://www.bkgm.com/faq/BasicRules.html#can_i_play_low_number_first_when_bearing_off_
         let start = Position::make(
@@ -294,6 +397,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::unused_unit)]
     fn test_movegen_bearing_off_2() -> () {
         // Example from https://www.bkgm.com/faq/BasicRules.html#can_i_play_low_number_first_when_bearing_off_
         let start = Position::make(&[(1, 1), (3, 3), (6, 1)], &[(23, 3), (24, 2)], 0, 0, 10, 10);
@@ -21,6 +21,10 @@ impl CheckerMove {
         self.to - 1
     }
 
+    pub fn hits(&self) -> bool {
+        self.hits
+    }
+
     pub fn is_entering(&self) -> bool {
         self.from == 25
     }
@@ -28,6 +32,12 @@ impl CheckerMove {
     pub fn is_bearing_off(&self) -> bool {
         self.to == 0
     }
+
+    /// Whether the move goes forward, from a point (or the bar) to a point (or off). Checkers on
+    /// the bar must enter before they can bear off.
+    pub fn is_valid(&self) -> bool {
+        self.to < self.from && self.from <= 25 && !(self.is_entering() && self.is_bearing_off())
+    }
 }
 
 impl fmt::Display for CheckerMove {
@@ -50,6 +60,10 @@ impl Move {
         }
     }
 
+    pub fn roll(&self) -> &Dice {
+        &self.roll
+    }
+
     pub fn moves(&self) -> &Vec<CheckerMove> {
         &self.checker_moves
     }
@@ -0,0 +1,104 @@
+use super::cube::{position_cube_decision, CubeAction, CubeContext};
+use super::dice::Dice;
+use super::eval::{CubeOwnership, Evaluator};
+use super::game::Resignation;
+use super::met::{MatchEquityTable, MatchScore};
+use super::position::Position;
+use super::search::{accept_resignation, best_o_play};
+
+/// What a player knows when making a decision, from their point of view as `o`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Situation {
+    pub position: Position,
+    pub cube_value: u32,
+    pub cube_owner: CubeOwnership,
+    /// The score from `o`'s point of view, or `None` for money play.
+    pub score: Option<MatchScore>,
+}
+
+/// The decisions a bot makes in a game, whatever it plays on. Each decision is made as `o`.
+pub trait Policy {
+    /// The position to play to after rolling `dice`, with `o` on roll. Must be legal.
+    fn play(&mut self, situation: &Situation, dice: &Dice) -> Position;
+
+    /// Whether to double before rolling, with `o` on roll.
+    fn double(&mut self, situation: &Situation) -> bool;
+
+    /// Whether to take the double `x` offers before rolling, with `x` on roll.
+    fn take(&mut self, situation: &Situation) -> bool;
+
+    /// Whether to accept the resignation `x` offers, with `x` on roll.
+    fn accept_resignation(&mut self, _situation: &Situation, _resignation: Resignation) -> bool {
+        false
+    }
+}
+
+/// Plays the best move by 0-ply evaluation, and makes cube and resignation decisions at the
+/// given ply, using the built-in match equity table in match play.
+pub struct EvaluatorPolicy<E: Evaluator> {
+    evaluator: E,
+    ply: u8,
+    met: MatchEquityTable,
+}
+
+impl<E: Evaluator> EvaluatorPolicy<E> {
+    pub fn make(evaluator: E, ply: u8) -> EvaluatorPolicy<E> {
+        EvaluatorPolicy {
+            evaluator,
+            ply,
+            met: MatchEquityTable::built_in(),
+        }
+    }
+
+    fn context(
+        &self,
+        value: u32,
+        owner: CubeOwnership,
+        score: Option<MatchScore>,
+    ) -> CubeContext<'_> {
+        match score {
+            Some(score) => CubeContext::match_play(value, owner, score, &self.met),
+            None => CubeContext::money(value, owner),
+        }
+    }
+}
+
+impl<E: Evaluator> Policy for EvaluatorPolicy<E> {
+    fn play(&mut self, situation: &Situation, dice: &Dice) -> Position {
+        best_o_play(&self.evaluator, &situation.position, dice)
+    }
+
+    fn double(&mut self, situation: &Situation) -> bool {
+        let context = self.context(situation.cube_value, situation.cube_owner, situation.score);
+        if !context.can_double() {
+            return false;
+        }
+
+        let decision =
+            position_cube_decision(&self.evaluator, &situation.position, self.ply, &context);
+        matches!(
+            decision.action,
+            CubeAction::DoubleTake | CubeAction::DoublePass
+        )
+    }
+
+    fn take(&mut self, situation: &Situation) -> bool {
+        // The decision is made from the point of view of `x`, who doubles.
+        let context = self.context(
+            situation.cube_value,
+            situation.cube_owner.invert(),
+            situation.score.map(|s| s.invert()),
+        );
+        position_cube_decision(
+            &self.evaluator,
+            &situation.position.flip(),
+            self.ply,
+            &context,
+        )
+        .is_take()
+    }
+
+    fn accept_resignation(&mut self, situation: &Situation, resignation: Resignation) -> bool {
+        accept_resignation(&self.evaluator, &situation.position, resignation, self.ply)
+    }
+}
@@ -1,6 +1,25 @@
-use super::moves::Move;
+use super::moves::{CheckerMove, Move};
 use std::fmt;
 
+// The alphabet of GNU Backgammon's position IDs.
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// One of the two players.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum Side {
+    O,
+    X,
+}
+
+impl Side {
+    pub fn opponent(&self) -> Side {
+        match self {
+            Side::O => Side::X,
+            Side::X => Side::O,
+        }
+    }
+}
+
 #[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
 pub struct Position {
     o_points: [u8; 24],
@@ -28,6 +47,15 @@ impl Position {
         self.x_bar
     }
 
+    /// The number of `o` checkers borne off.
+    pub fn o_home_value(&self) -> u8 {
+        self.o_home
+    }
+
+    pub fn x_home_value(&self) -> u8 {
+        self.x_home
+    }
+
     pub fn o_can_bear_off(&self) -> bool {
         self.o_home
             + self.point_o_value(0)
@@ -269,6 +297,95 @@ impl Position {
         }
     }
 
+    /// GNU Backgammon's position ID, with `o` on roll, e.g. `4HPwATDgc/ABMA` for the initial
+    /// position.
+    ///
+    /// The ID encodes 80 bits in base 64: for `x` then `o`, and for each of their points from
+    /// their own 1 to 24 and then the bar, a 1 per checker followed by a 0.
+    pub fn id(&self) -> String {
+        let mut counts = Vec::with_capacity(50);
+        counts.extend(self.x_points.iter().rev());
+        counts.push(self.x_bar);
+        counts.extend(self.o_points.iter());
+        counts.push(self.o_bar);
+
+        let mut key = [0u8; 10];
+        let mut bit = 0;
+        for count in counts {
+            for _ in 0..count {
+                key[bit / 8] |= 1 << (bit % 8);
+                bit += 1;
+            }
+            bit += 1;
+        }
+
+        // 14 characters of 6 bits, filled from the high bits of each byte.
+        let mut id = String::with_capacity(14);
+        for i in 0..14 {
+            let mut value = 0;
+            for j in 0..6 {
+                let bit = i * 6 + j;
+                let byte = key.get(bit / 8).copied().unwrap_or(0);
+                value = value << 1 | ((byte >> (7 - bit % 8)) & 1);
+            }
+            id.push(char::from(BASE64[usize::from(value)]));
+        }
+        id
+    }
+
+    /// Reads a GNU Backgammon position ID, with the player on roll as `o`.
+    pub fn from_id(id: &str) -> Option<Position> {
+        if id.len() != 14 {
+            return None;
+        }
+
+        let mut key = [0u8; 11];
+        for (i, c) in id.bytes().enumerate() {
+            let value = BASE64.iter().position(|&b| b == c)?;
+            for j in 0..6 {
+                let bit = i * 6 + j;
+                if (value >> (5 - j)) & 1 == 1 {
+                    key[bit / 8] |= 1 << (7 - bit % 8);
+                }
+            }
+        }
+
+        let mut counts = [0u8; 50];
+        let mut slot = 0;
+        for bit in 0..80 {
+            if slot == counts.len() {
+                break;
+            }
+            if (key[bit / 8] >> (bit % 8)) & 1 == 1 {
+                counts[slot] += 1;
+            } else {
+                slot += 1;
+            }
+        }
+
+        let mut x_points = [0; 24];
+        x_points.copy_from_slice(&counts[..24]);
+        x_points.reverse();
+        let mut o_points = [0; 24];
+        o_points.copy_from_slice(&counts[25..49]);
+
+        let x_checkers: u8 = counts[..25].iter().sum();
+        let o_checkers: u8 = counts[25..].iter().sum();
+        if x_checkers > 15 || o_checkers > 15 {
+            return None;
+        }
+
+        let position = Position {
+            o_points,
+            x_points,
+            o_bar: counts[49],
+            x_bar: counts[24],
+            o_home: 15 - o_checkers,
+            x_home: 15 - x_checkers,
+        };
+        Some(position).filter(|p| p.is_valid())
+    }
+
     pub fn apply_o_move(&self, o_move: &Move) -> Position {
         let mut result: Position = *self;
         for checker_move in o_move.moves() {
@@ -286,6 +403,51 @@ impl Position {
     pub fn apply_x_move(&self, x_move: &Move) -> Position {
         self.flip().apply_o_move(x_move).flip()
     }
+
+    /// Like `apply_o_move`, but returns `None` instead of panicking if any checker move doesn't
+    /// apply to the position.
+    pub fn try_apply_o_move(&self, o_move: &Move) -> Option<Position> {
+        o_move
+            .moves()
+            .iter()
+            .try_fold(*self, |result, checker_move| {
+                result.try_apply_o_checker_move(checker_move)
+            })
+    }
+
+    /// Moves a single checker, if possible. Hit markers are ignored.
+    pub fn try_apply_o_checker_move(&self, checker_move: &CheckerMove) -> Option<Position> {
+        if !checker_move.is_valid() {
+            return None;
+        }
+
+        if checker_move.is_entering() {
+            let to = checker_move.dst_point();
+            if self.o_bar_value() < 1 || self.point_x_value(to) > 1 {
+                return None;
+            }
+            Some(self.with_o_entering(to))
+        } else if self.o_bar_value() > 0 {
+            // Checkers on the bar must enter first.
+            None
+        } else if checker_move.is_bearing_off() {
+            let from = checker_move.src_point();
+            if !self.o_can_bear_off() || self.point_o_value(from) < 1 {
+                return None;
+            }
+            Some(self.with_o_bearing_off(from))
+        } else {
+            let (from, to) = (checker_move.src_point(), checker_move.dst_point());
+            if self.point_o_value(from) < 1 || self.point_x_value(to) > 1 {
+                return None;
+            }
+            Some(self.with_o_move(from, to))
+        }
+    }
+
+    pub fn try_apply_x_move(&self, x_move: &Move) -> Option<Position> {
+        self.flip().try_apply_o_move(x_move).map(|p| p.flip())
+    }
 }
 
 impl fmt::Display for Position {
@@ -371,6 +533,7 @@ mod tests {
     use super::*;
 
     #[test]
+    #[allow(clippy::unused_unit)]
     fn test_flip_initial() -> () {
         let p1 = Position::initial();
         let p2 = p1.flip();
@@ -379,6 +542,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::unused_unit)]
     fn test_flip_twice() -> () {
         let p1 = Position::make(
             &[(6, 5), (8, 3), (13, 5), (24, 1)],
@@ -392,4 +556,56 @@ mod tests {
         assert_ne!(p1, p1.flip());
         assert_eq!(p1, p1.flip().flip());
     }
+
+    #[test]
+    fn test_position_id() {
+        assert_eq!("4HPwATDgc/ABMA", Position::initial().id());
+        assert_eq!(Some(Position::initial()), Position::from_id("4HPwATDgc/ABMA"));
+
+        let p = Position::make(
+            &[(6, 5), (8, 3), (13, 5), (24, 1)],
+            &[(1, 2), (7, 2), (12, 2), (17, 2), (18, 2), (19, 4)],
+            1,
+            0,
+            0,
+            1,
+        );
+        assert_eq!(Some(p), Position::from_id(&p.id()));
+        assert_eq!(Some(p.flip()), Position::from_id(&p.flip().id()));
+
+        assert_eq!(None, Position::from_id("4HPwATDgc/ABM"));
+        assert_eq!(None, Position::from_id("4HPwATDgc/AB.A"));
+        // 30 checkers for `x`.
+        assert_eq!(None, Position::from_id("////////AAAAAA"));
+    }
+
+    #[test]
+    fn test_try_apply_move() {
+        use crate::dice::Dice;
+        use crate::moves::CheckerMove;
+
+        let p = Position::initial();
+        let play = Move::make(
+            Dice::make(3, 1),
+            vec![CheckerMove::make(8, 5, false), CheckerMove::make(6, 5, false)],
+        );
+        assert_eq!(Some(p.apply_o_move(&play)), p.try_apply_o_move(&play));
+
+        // The 12 point is x's.
+        let blocked = Move::make(Dice::make(1, 1), vec![CheckerMove::make(13, 12, false)]);
+        assert_eq!(None, p.try_apply_o_move(&blocked));
+
+        let empty = Move::make(Dice::make(2, 1), vec![CheckerMove::make(23, 21, false)]);
+        assert_eq!(None, p.try_apply_o_move(&empty));
+        assert!(p.try_apply_x_move(&empty).is_none());
+
+        let off = Move::make(Dice::make(6, 1), vec![CheckerMove::make(6, 0, false)]);
+        assert_eq!(None, p.try_apply_o_move(&off));
+
+        // Checkers on the bar can't bear off.
+        let hit = Position::make(&[(1, 14)], &[(24, 15)], 1, 0, 0, 0);
+        let bar_off = Move::make(Dice::make(6, 1), vec![CheckerMove::make(25, 0, false)]);
+        assert_eq!(None, hit.try_apply_o_move(&bar_off));
+        assert_eq!(None, hit.flip().try_apply_x_move(&bar_off));
+    }
 }
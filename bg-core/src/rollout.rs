@@ -1,9 +1,11 @@
 use rand::Rng;
 
 use super::dice::Dice;
+use super::eval::Evaluator;
 use super::movegen::generate_o_moves;
 use super::movegen::generate_x_moves;
 use super::position::Position;
+use super::search::best_o_play;
 
 #[derive(Debug)]
 pub struct RolloutStats {
@@ -19,6 +21,49 @@ pub struct RolloutStats {
 const ROLLOUT_ROLLS: u64 = 1000;
 
 pub fn rollout_o(position: &Position) -> RolloutStats {
+    rollout_o_games(position, ROLLOUT_ROLLS)
+}
+
+/// Plays `games` random games from `position`, with `o` on roll.
+pub fn rollout_o_games(position: &Position, games: u64) -> RolloutStats {
+    play_o_games(position, games, |p, roll, o_turn| {
+        let moves: Vec<Position> = if o_turn {
+            generate_o_moves(p, roll).into_iter().collect()
+        } else {
+            generate_x_moves(p, roll).into_iter().collect()
+        };
+
+        if moves.is_empty() {
+            *p
+        } else {
+            moves[rand::thread_rng().gen_range(0..moves.len())]
+        }
+    })
+}
+
+/// Plays `games` cubeless games from `position`, with `o` on roll, in which both sides make the
+/// play `evaluator` prefers at 0-ply.
+pub fn rollout_o_games_with<E: Evaluator>(
+    evaluator: &E,
+    position: &Position,
+    games: u64,
+) -> RolloutStats {
+    play_o_games(position, games, |p, roll, o_turn| {
+        if o_turn {
+            best_o_play(evaluator, p, roll)
+        } else {
+            best_o_play(evaluator, &p.flip(), roll).flip()
+        }
+    })
+}
+
+// Plays the games with the plays `play` picks, given the position, the roll and whether `o`
+// is on roll.
+fn play_o_games<F: FnMut(&Position, &Dice, bool) -> Position>(
+    position: &Position,
+    games: u64,
+    mut play: F,
+) -> RolloutStats {
     let mut o_wins = 0;
     let mut o_gammons = 0;
     let mut o_backgammons = 0;
@@ -26,22 +71,13 @@ pub fn rollout_o(position: &Position) -> RolloutStats {
     let mut x_gammons = 0;
     let mut x_backgammons = 0;
 
-    for _ in 0..ROLLOUT_ROLLS {
+    for _ in 0..games {
         let mut p = *position;
         let mut o_turn: bool = true;
 
         loop {
             let roll = Dice::roll();
-
-            let moves: Vec<Position> = if o_turn {
-                generate_o_moves(&p, &roll).into_iter().collect()
-            } else {
-                generate_x_moves(&p, &roll).into_iter().collect()
-            };
-
-            if !moves.is_empty() {
-                p = moves[rand::thread_rng().gen_range(0..moves.len())];
-            }
+            p = play(&p, &roll, o_turn);
 
             if p.o_has_won() {
                 o_wins += 1;
@@ -70,10 +106,10 @@ pub fn rollout_o(position: &Position) -> RolloutStats {
         }
     }
 
-    let rolls = ROLLOUT_ROLLS as f64;
+    let rolls = games as f64;
 
     RolloutStats {
-        rolls: ROLLOUT_ROLLS,
+        rolls: games,
         o_win: f64::from(o_wins) / rolls,
         o_gammon: f64::from(o_gammons) / rolls,
         o_backgammon: f64::from(o_backgammons) / rolls,
@@ -0,0 +1,318 @@
+use std::cmp::Ordering;
+
+use super::cache::CacheKey;
+use super::dice::{all_rolls, Dice};
+use super::eval::{Evaluation, Evaluator};
+use super::game::Resignation;
+use super::movegen::generate_o_moves;
+use super::position::Position;
+
+/// Decides which candidate plays survive to be evaluated at the next ply, in the style of
+/// GNU Backgammon: the best `accept` plays are always kept, and up to `extra` more are kept
+/// if their equity is within `threshold` of the best one. The best play is never pruned.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MoveFilter {
+    pub accept: usize,
+    pub extra: usize,
+    pub threshold: f64,
+}
+
+impl MoveFilter {
+    pub fn make(accept: usize, extra: usize, threshold: f64) -> MoveFilter {
+        MoveFilter {
+            accept,
+            extra,
+            threshold,
+        }
+    }
+
+    /// How many of `equities`, sorted best first, should be kept.
+    fn keep(&self, equities: &[f64]) -> usize {
+        let best = match equities.first() {
+            Some(e) => *e,
+            None => return 0,
+        };
+
+        let accepted = self.accept.max(1).min(equities.len());
+        let extra = equities[accepted..]
+            .iter()
+            .take(self.extra)
+            .take_while(|&&e| best - e <= self.threshold)
+            .count();
+
+        accepted + extra
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchConfig {
+    /// How deep the surviving candidates are evaluated. 0-ply is a static evaluation.
+    pub plies: u8,
+    /// `filters[n]` prunes candidates before they're evaluated at ply `n + 1`. If there are
+    /// fewer filters than plies, the last one is reused.
+    pub filters: Vec<MoveFilter>,
+}
+
+impl SearchConfig {
+    pub fn make(plies: u8, filters: Vec<MoveFilter>) -> SearchConfig {
+        SearchConfig { plies, filters }
+    }
+
+    fn filter(&self, ply: u8) -> MoveFilter {
+        let i = usize::from(ply).min(self.filters.len().saturating_sub(1));
+        self.filters
+            .get(i)
+            .copied()
+            .unwrap_or_else(|| MoveFilter::make(1, 0, 0.0))
+    }
+}
+
+impl Default for SearchConfig {
+    /// 2-ply, with filters similar to GNU Backgammon's "normal" setting.
+    fn default() -> SearchConfig {
+        SearchConfig::make(
+            2,
+            vec![MoveFilter::make(0, 8, 0.16), MoveFilter::make(0, 2, 0.04)],
+        )
+    }
+}
+
+/// A candidate play, with the evaluation of the position after it for the player who moved.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RankedPlay {
+    pub position: Position,
+    pub evaluation: Evaluation,
+    pub equity: f64,
+    /// The depth at which this play was last evaluated.
+    pub ply: u8,
+}
+
+/// Ranks all plays for `o` with the given roll, best first.
+///
+/// Plays that survived all filters come first, evaluated at `config.plies`; pruned plays follow,
+/// with the evaluation from the ply at which they were dropped. If `o` cannot move, the only
+/// "play" is the unchanged position.
+pub fn rank_o_plays<E: Evaluator>(
+    evaluator: &E,
+    position: &Position,
+    dice: &Dice,
+    config: &SearchConfig,
+) -> Vec<RankedPlay> {
+    let mut candidates: Vec<Position> = generate_o_moves(position, dice).into_iter().collect();
+    if candidates.is_empty() {
+        candidates.push(*position);
+    }
+
+    let mut live: Vec<RankedPlay> = candidates
+        .into_iter()
+        .map(|p| ranked(p, evaluate_after_move(evaluator, &p, 0), 0))
+        .collect();
+    let mut pruned: Vec<RankedPlay> = Vec::new();
+
+    for ply in 1..=config.plies {
+        sort_plays(&mut live);
+        let equities: Vec<f64> = live.iter().map(|p| p.equity).collect();
+        let keep = config.filter(ply - 1).keep(&equities);
+        pruned.extend(live.drain(keep..));
+
+        for play in live.iter_mut() {
+            *play = ranked(
+                play.position,
+                evaluate_after_move(evaluator, &play.position, ply),
+                ply,
+            );
+        }
+    }
+
+    sort_plays(&mut live);
+    sort_plays(&mut pruned);
+    live.extend(pruned);
+    live
+}
+
+/// Like `rank_o_plays`, for `x`. Positions are returned from the usual `o` point of view, but
+/// evaluations and equities are for `x`.
+pub fn rank_x_plays<E: Evaluator>(
+    evaluator: &E,
+    position: &Position,
+    dice: &Dice,
+    config: &SearchConfig,
+) -> Vec<RankedPlay> {
+    rank_o_plays(evaluator, &position.flip(), dice, config)
+        .into_iter()
+        .map(|play| RankedPlay {
+            position: play.position.flip(),
+            ..play
+        })
+        .collect()
+}
+
+/// The best play for `o` by 0-ply evaluation, or the unchanged position if `o` cannot move.
+pub fn best_o_play<E: Evaluator>(evaluator: &E, position: &Position, dice: &Dice) -> Position {
+    generate_o_moves(position, dice)
+        .into_iter()
+        .map(|p| (evaluate_after_move(evaluator, &p, 0).equity(), p))
+        .max_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal))
+        .map(|(_, p)| p)
+        .unwrap_or(*position)
+}
+
+/// Evaluates `position`, with `o` on roll, looking `ply` half-moves ahead.
+///
+/// Beyond 0-ply, this averages over all 21 rolls. For each roll, the play is chosen by 0-ply
+/// evaluation and then evaluated at one ply less.
+pub fn evaluate_on_roll<E: Evaluator>(evaluator: &E, position: &Position, ply: u8) -> Evaluation {
+    if let Some(evaluation) = Evaluation::terminal(position) {
+        return evaluation;
+    }
+
+    if ply == 0 {
+        return evaluator.evaluate(position);
+    }
+
+    let key = CacheKey::make(*position, ply);
+    if let Some(evaluation) = evaluator.cached(&key) {
+        return evaluation;
+    }
+
+    let mut total = Evaluation::make(0.0, 0.0, 0.0, 0.0, 0.0);
+
+    for (dice, weight) in all_rolls().iter() {
+        let best = best_o_play(evaluator, position, dice);
+        let e = evaluate_after_move(evaluator, &best, ply - 1);
+        total.win += weight * e.win;
+        total.win_gammon += weight * e.win_gammon;
+        total.win_backgammon += weight * e.win_backgammon;
+        total.lose_gammon += weight * e.lose_gammon;
+        total.lose_backgammon += weight * e.lose_backgammon;
+    }
+
+    evaluator.store(key, total);
+    total
+}
+
+/// Evaluates `position` for `o`, right after `o` moved, i.e. with `x` on roll.
+pub fn evaluate_after_move<E: Evaluator>(
+    evaluator: &E,
+    position: &Position,
+    ply: u8,
+) -> Evaluation {
+    if let Some(evaluation) = Evaluation::terminal(position) {
+        return evaluation;
+    }
+
+    evaluate_on_roll(evaluator, &position.flip(), ply).invert()
+}
+
+/// Whether `o` should accept the resignation `x` offers while on roll, i.e. whether it's worth
+/// at least `o`'s cubeless equity at the given ply. The cube value scales both sides equally,
+/// so it doesn't matter here.
+pub fn accept_resignation<E: Evaluator>(
+    evaluator: &E,
+    position: &Position,
+    resignation: Resignation,
+    ply: u8,
+) -> bool {
+    let equity = evaluate_after_move(evaluator, position, ply).equity();
+    f64::from(resignation.points()) >= equity
+}
+
+fn ranked(position: Position, evaluation: Evaluation, ply: u8) -> RankedPlay {
+    RankedPlay {
+        position,
+        evaluation,
+        equity: evaluation.equity(),
+        ply,
+    }
+}
+
+fn sort_plays(plays: &mut [RankedPlay]) {
+    plays.sort_by(|a, b| b.equity.partial_cmp(&a.equity).unwrap_or(Ordering::Equal));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bearoff::{BearoffDatabase, BearoffEvaluator};
+    use crate::eval::RolloutEvaluator;
+
+    #[test]
+    fn test_move_filter() {
+        let equities = [0.5, 0.45, 0.3, 0.2, -0.1];
+
+        assert_eq!(1, MoveFilter::make(0, 0, 1.0).keep(&equities));
+        assert_eq!(2, MoveFilter::make(0, 8, 0.1).keep(&equities));
+        assert_eq!(3, MoveFilter::make(3, 8, 0.1).keep(&equities));
+        assert_eq!(4, MoveFilter::make(1, 3, 1.0).keep(&equities));
+        assert_eq!(5, MoveFilter::make(8, 0, 0.0).keep(&equities));
+        assert_eq!(0, MoveFilter::make(1, 1, 0.0).keep(&[]));
+    }
+
+    #[test]
+    fn test_search_exact_in_bearoff() {
+        let db = BearoffDatabase::generate(6, 3, false);
+        let evaluator = BearoffEvaluator::make(db, RolloutEvaluator);
+
+        let start = Position::make(&[(2, 1), (5, 1), (6, 1)], &[(19, 1), (23, 2)], 0, 0, 12, 12);
+        let dice = Dice::make(4, 1);
+
+        let zero = rank_o_plays(&evaluator, &start, &dice, &SearchConfig::make(0, vec![]));
+        let two = rank_o_plays(&evaluator, &start, &dice, &SearchConfig::default());
+
+        assert_eq!(zero.len(), two.len());
+        assert_eq!(zero[0].position, two[0].position);
+        assert_eq!(2, two[0].ply);
+
+        // The database is exact, so looking ahead can't change its verdict.
+        assert!((zero[0].equity - two[0].equity).abs() < 1e-5);
+
+        // And the best play's value is the position's value.
+        let on_roll = evaluator.database().cubeless_equity(&start).unwrap();
+        let all_rolls_best: f64 = evaluate_on_roll(&evaluator, &start, 1).equity();
+        assert!((on_roll - all_rolls_best).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_accept_resignation() {
+        // `x` on roll is losing, with many gammons.
+        struct Losing;
+        impl Evaluator for Losing {
+            fn evaluate(&self, _: &Position) -> Evaluation {
+                Evaluation::make(0.1, 0.0, 0.0, 0.6, 0.0)
+            }
+        }
+
+        let p = Position::initial();
+        assert!(!accept_resignation(&Losing, &p, Resignation::Single, 0));
+        assert!(accept_resignation(&Losing, &p, Resignation::Gammon, 0));
+        assert!(accept_resignation(&Losing, &p, Resignation::Backgammon, 0));
+    }
+
+    #[test]
+    fn test_search_no_move() {
+        let start = Position::make(
+            &[(6, 5), (8, 3), (13, 5), (24, 1)],
+            &[(1, 2), (7, 2), (12, 2), (17, 2), (18, 2), (19, 5)],
+            1,
+            0,
+            0,
+            0,
+        );
+
+        struct Flat;
+        impl Evaluator for Flat {
+            fn evaluate(&self, _: &Position) -> Evaluation {
+                Evaluation::make(0.5, 0.0, 0.0, 0.0, 0.0)
+            }
+        }
+
+        let plays = rank_o_plays(
+            &Flat,
+            &start,
+            &Dice::make(6, 6),
+            &SearchConfig::make(1, vec![]),
+        );
+        assert_eq!(1, plays.len());
+        assert_eq!(start, plays[0].position);
+    }
+}
@@ -0,0 +1,971 @@
+use std::error::Error;
+use std::fmt;
+
+use rand::Rng;
+
+use super::dice::Dice;
+use super::game::{Action, Cube, Game, Match, Resignation};
+use super::met::MatchScore;
+use super::movegen::{find_o_move, find_x_move, generate_o_moves, generate_x_moves};
+use super::position::{Position, Side};
+
+/// Where a game is in its turn sequence.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Phase {
+    /// Each player rolls one die to decide who starts.
+    Opening,
+    /// The player may double, or roll.
+    Rolling(Side),
+    /// The player must roll: a cube action was just taken on their turn.
+    MustRoll(Side),
+    /// The player has rolled and must play.
+    Moving(Side, Dice),
+    /// The player must take or drop a double, or beaver it if allowed.
+    Doubled(Side),
+    /// The player was beavered, and may raccoon before rolling.
+    Beavered(Side),
+    /// The player must accept or reject a resignation.
+    Resigned(Side, Resignation),
+    Over {
+        winner: Side,
+        points: u32,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlayError {
+    /// The action isn't allowed in the current phase.
+    OutOfTurn,
+    /// The cube isn't available to the player on roll.
+    CannotDouble,
+    /// The position can't be reached with the roll.
+    IllegalPlay,
+}
+
+impl fmt::Display for PlayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlayError::OutOfTurn => write!(f, "not allowed at this point of the game"),
+            PlayError::CannotDouble => write!(f, "the cube is not available"),
+            PlayError::IllegalPlay => write!(f, "illegal play"),
+        }
+    }
+}
+
+impl Error for PlayError {}
+
+/// Rules that may change from one game to the next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct GameRules {
+    /// The cube cannot be used in the Crawford game.
+    pub crawford: bool,
+    /// This player doubles automatically as soon as they can, as the trailer should after the
+    /// Crawford game.
+    pub auto_double: Option<Side>,
+    /// Gammons and backgammons only count once the cube has been turned.
+    pub jacoby: bool,
+    /// A player who is doubled may take and immediately redouble, keeping the cube.
+    pub beavers: bool,
+    /// A player who is beavered may redouble again, before rolling.
+    pub raccoons: bool,
+    /// The most times the cube is doubled on tied opening rolls. 0 disables automatic doubles,
+    /// and anything over 31 counts as 31.
+    pub auto_doubles: u32,
+}
+
+impl GameRules {
+    /// The usual rules for money play: Jacoby, beavers and raccoons, no automatic doubles.
+    pub fn money() -> GameRules {
+        GameRules {
+            jacoby: true,
+            beavers: true,
+            raccoons: true,
+            ..GameRules::default()
+        }
+    }
+}
+
+/// A game being played. It enforces the turn sequence, and records the game as it goes.
+pub struct GameState {
+    position: Position,
+    cube: Cube,
+    phase: Phase,
+    /// Where play resumes if a resignation is rejected.
+    resumed: Phase,
+    rules: GameRules,
+    game: Game,
+}
+
+impl GameState {
+    /// A new game, waiting for the opening roll.
+    pub fn make(rules: GameRules) -> GameState {
+        let mut game = Game::make(None, vec![]);
+        game.crawford = rules.crawford;
+
+        GameState {
+            position: Position::initial(),
+            cube: Cube::initial(),
+            phase: Phase::Opening,
+            resumed: Phase::Opening,
+            rules,
+            game,
+        }
+    }
+
+    pub fn rules(&self) -> &GameRules {
+        &self.rules
+    }
+
+    pub fn position(&self) -> &Position {
+        &self.position
+    }
+
+    pub fn cube(&self) -> &Cube {
+        &self.cube
+    }
+
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    /// The game record so far.
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    pub fn into_game(self) -> Game {
+        self.game
+    }
+
+    pub fn is_over(&self) -> bool {
+        matches!(self.phase, Phase::Over { .. })
+    }
+
+    /// Each player rolls one die, and the higher one starts by playing both. Returns the
+    /// starting side, or `None` on a tie, in which case both roll again, and the cube is turned
+    /// if automatic doubles are on.
+    pub fn opening_roll(&mut self, o_die: u8, x_die: u8) -> Result<Option<Side>, PlayError> {
+        if self.phase != Phase::Opening {
+            return Err(PlayError::OutOfTurn);
+        }
+
+        if o_die == x_die {
+            let limit = 1u32.checked_shl(self.rules.auto_doubles).unwrap_or(1 << 31);
+            if self.cube.value < limit {
+                self.cube.value *= 2;
+            }
+            return Ok(None);
+        }
+
+        let side = if o_die > x_die { Side::O } else { Side::X };
+        self.game.starting_side = Some(side);
+        self.phase = Phase::Moving(side, Dice::make(o_die, x_die));
+        Ok(Some(side))
+    }
+
+    /// Rolls the opening dice until they differ, and returns the starting side.
+    pub fn roll_for_start(&mut self) -> Result<Side, PlayError> {
+        let mut rng = rand::thread_rng();
+        loop {
+            if let Some(side) = self.opening_roll(rng.gen_range(1..=6), rng.gen_range(1..=6))? {
+                return Ok(side);
+            }
+        }
+    }
+
+    pub fn can_double(&self) -> bool {
+        match self.phase {
+            Phase::Rolling(side) => {
+                !self.rules.crawford && self.cube.owner != Some(side.opponent())
+            }
+            _ => false,
+        }
+    }
+
+    pub fn double(&mut self) -> Result<(), PlayError> {
+        let side = self.rolling_side()?;
+        if !self.can_double() {
+            return Err(PlayError::CannotDouble);
+        }
+
+        self.record(side, Action::Double(2 * self.cube.value));
+        self.phase = Phase::Doubled(side.opponent());
+        Ok(())
+    }
+
+    pub fn take(&mut self) -> Result<(), PlayError> {
+        let side = self.doubled_side()?;
+        self.record(side, Action::Take);
+        self.phase = Phase::MustRoll(side.opponent());
+        Ok(())
+    }
+
+    pub fn drop(&mut self) -> Result<(), PlayError> {
+        let side = self.doubled_side()?;
+        self.record(side, Action::Drop);
+        self.finish(side.opponent(), self.cube.value);
+        Ok(())
+    }
+
+    /// Takes the double and immediately redoubles, keeping the cube.
+    pub fn beaver(&mut self) -> Result<(), PlayError> {
+        let side = self.doubled_side()?;
+        if !self.rules.beavers {
+            return Err(PlayError::CannotDouble);
+        }
+
+        let value = 2 * self.cube.offered.unwrap_or(2 * self.cube.value);
+        self.record(side, Action::Beaver(value));
+        self.phase = if self.rules.raccoons {
+            Phase::Beavered(side.opponent())
+        } else {
+            Phase::MustRoll(side.opponent())
+        };
+        Ok(())
+    }
+
+    /// Redoubles after being beavered, keeping the cube. It's recorded as another beaver.
+    pub fn raccoon(&mut self) -> Result<(), PlayError> {
+        let side = match self.phase {
+            Phase::Beavered(side) => side,
+            _ => return Err(PlayError::OutOfTurn),
+        };
+
+        self.record(side, Action::Beaver(2 * self.cube.value));
+        self.phase = Phase::MustRoll(side);
+        Ok(())
+    }
+
+    /// Offers to resign, on one's own turn. The opponent must then accept or reject.
+    pub fn resign(&mut self, resignation: Resignation) -> Result<(), PlayError> {
+        let side = match self.phase {
+            Phase::Rolling(side) | Phase::MustRoll(side) | Phase::Moving(side, _) => side,
+            _ => return Err(PlayError::OutOfTurn),
+        };
+
+        self.record(side, Action::Resign(resignation));
+        self.resumed = self.phase;
+        self.phase = Phase::Resigned(side.opponent(), resignation);
+        Ok(())
+    }
+
+    pub fn accept(&mut self) -> Result<(), PlayError> {
+        let (side, resignation) = self.resigned_side()?;
+        self.record(side, Action::Accept);
+
+        let points = if self.rules.jacoby && self.cube.owner.is_none() {
+            1
+        } else {
+            resignation.points()
+        };
+        self.finish(side, self.cube.value * points);
+        Ok(())
+    }
+
+    pub fn reject(&mut self) -> Result<(), PlayError> {
+        let (side, _) = self.resigned_side()?;
+        self.record(side, Action::Reject);
+        self.phase = self.resumed;
+        Ok(())
+    }
+
+    pub fn roll(&mut self, dice: Dice) -> Result<(), PlayError> {
+        let side = match self.phase {
+            Phase::Rolling(side) | Phase::MustRoll(side) | Phase::Beavered(side) => side,
+            _ => return Err(PlayError::OutOfTurn),
+        };
+        // `Dice::roll` doesn't sort the dice, but recorded moves list the high die first.
+        self.phase = Phase::Moving(side, Dice::make(dice.high(), dice.low()));
+        Ok(())
+    }
+
+    /// The positions the player on roll can play to. If they cannot move, that's the current
+    /// position. Empty outside of the `Moving` phase.
+    pub fn legal_plays(&self) -> Vec<Position> {
+        let (side, dice) = match self.phase {
+            Phase::Moving(side, dice) => (side, dice),
+            _ => return vec![],
+        };
+
+        let plays: Vec<Position> = match side {
+            Side::O => generate_o_moves(&self.position, &dice),
+            Side::X => generate_x_moves(&self.position, &dice),
+        }
+        .into_iter()
+        .collect();
+
+        if plays.is_empty() {
+            vec![self.position]
+        } else {
+            plays
+        }
+    }
+
+    /// Plays to the given position, which must be one of the `legal_plays`.
+    pub fn play(&mut self, to: &Position) -> Result<(), PlayError> {
+        let (side, dice) = match self.phase {
+            Phase::Moving(side, dice) => (side, dice),
+            _ => return Err(PlayError::OutOfTurn),
+        };
+
+        let found = match side {
+            Side::O => find_o_move(&self.position, &dice, to),
+            Side::X => find_x_move(&self.position, &dice, to),
+        };
+        let m = found.ok_or(PlayError::IllegalPlay)?;
+
+        self.record(side, Action::Move(m));
+        self.position = *to;
+
+        if self.position.is_over() {
+            let multiplier = if self.rules.jacoby && self.cube.owner.is_none() {
+                1
+            } else {
+                result_multiplier(&self.position)
+            };
+            self.finish(side, self.cube.value * multiplier);
+        } else {
+            self.start_turn(side.opponent());
+        }
+        Ok(())
+    }
+
+    fn start_turn(&mut self, side: Side) {
+        self.phase = Phase::Rolling(side);
+
+        if self.rules.auto_double == Some(side) && self.cube.owner.is_none() && self.can_double() {
+            self.record(side, Action::Double(2 * self.cube.value));
+            self.phase = Phase::Doubled(side.opponent());
+        }
+    }
+
+    fn rolling_side(&self) -> Result<Side, PlayError> {
+        match self.phase {
+            Phase::Rolling(side) | Phase::MustRoll(side) => Ok(side),
+            _ => Err(PlayError::OutOfTurn),
+        }
+    }
+
+    fn resigned_side(&self) -> Result<(Side, Resignation), PlayError> {
+        match self.phase {
+            Phase::Resigned(side, resignation) => Ok((side, resignation)),
+            _ => Err(PlayError::OutOfTurn),
+        }
+    }
+
+    fn doubled_side(&self) -> Result<Side, PlayError> {
+        match self.phase {
+            Phase::Doubled(side) => Ok(side),
+            _ => Err(PlayError::OutOfTurn),
+        }
+    }
+
+    // Turns hold `o`'s action then `x`'s, so `x` completes the last turn if it can.
+    fn record(&mut self, side: Side, action: Action) {
+        self.cube.apply(side, &action);
+
+        match (side, self.game.turns.last_mut()) {
+            (Side::X, Some((_, x_action @ None))) => *x_action = Some(action),
+            (Side::X, _) => self.game.turns.push((None, Some(action))),
+            (Side::O, _) => self.game.turns.push((Some(action), None)),
+        }
+    }
+
+    fn finish(&mut self, winner: Side, points: u32) {
+        self.phase = Phase::Over { winner, points };
+        self.game.winner = Some(winner);
+        self.game.points = points;
+    }
+}
+
+/// A match being played, one `GameState` at a time.
+pub struct MatchState {
+    length: u32,
+    o_player: String,
+    x_player: String,
+    o_score: u32,
+    x_score: u32,
+    /// Whether the trailer automatically doubles in each post-Crawford game.
+    auto_double: bool,
+    crawford_played: bool,
+    games: Vec<Game>,
+    current: Option<GameState>,
+}
+
+impl MatchState {
+    pub fn make(length: u32, o_player: &str, x_player: &str, auto_double: bool) -> MatchState {
+        let mut state = MatchState {
+            length,
+            o_player: o_player.to_string(),
+            x_player: x_player.to_string(),
+            o_score: 0,
+            x_score: 0,
+            auto_double,
+            crawford_played: false,
+            games: vec![],
+            current: None,
+        };
+        state.current = Some(GameState::make(state.next_rules()));
+        state
+    }
+
+    pub fn length(&self) -> u32 {
+        self.length
+    }
+
+    pub fn o_score(&self) -> u32 {
+        self.o_score
+    }
+
+    pub fn x_score(&self) -> u32 {
+        self.x_score
+    }
+
+    /// The game being played, or `None` once the match is over.
+    pub fn game(&self) -> Option<&GameState> {
+        self.current.as_ref()
+    }
+
+    pub fn game_mut(&mut self) -> Option<&mut GameState> {
+        self.current.as_mut()
+    }
+
+    /// The score of the current game, from `side`'s point of view.
+    pub fn score(&self, side: Side) -> MatchScore {
+        let crawford = self.current.as_ref().is_some_and(|g| g.rules().crawford);
+        let o_away = self.length.saturating_sub(self.o_score);
+        let x_away = self.length.saturating_sub(self.x_score);
+
+        match side {
+            Side::O => MatchScore::make(o_away, x_away, crawford),
+            Side::X => MatchScore::make(x_away, o_away, crawford),
+        }
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.winner().is_some()
+    }
+
+    pub fn winner(&self) -> Option<Side> {
+        if self.o_score >= self.length {
+            Some(Side::O)
+        } else if self.x_score >= self.length {
+            Some(Side::X)
+        } else {
+            None
+        }
+    }
+
+    /// Records the current game, which must be over, and starts the next one unless the match
+    /// is over.
+    pub fn finish_game(&mut self) -> Result<(), PlayError> {
+        let state = match self.current.take() {
+            Some(state) if state.is_over() => state,
+            other => {
+                self.current = other;
+                return Err(PlayError::OutOfTurn);
+            }
+        };
+
+        let mut game = state.into_game();
+        game.number = self.games.len() as u32 + 1;
+        game.o_player = self.o_player.clone();
+        game.x_player = self.x_player.clone();
+        game.o_score = self.o_score;
+        game.x_score = self.x_score;
+
+        match game.winner {
+            Some(Side::O) => self.o_score += game.points,
+            Some(Side::X) => self.x_score += game.points,
+            None => (),
+        }
+        self.games.push(game);
+
+        if !self.is_over() {
+            self.current = Some(GameState::make(self.next_rules()));
+        }
+        Ok(())
+    }
+
+    /// The record of the games played so far.
+    pub fn into_match(self) -> Match {
+        Match::make(self.length, self.games)
+    }
+
+    fn next_rules(&mut self) -> GameRules {
+        let o_one_away = self.o_score + 1 == self.length;
+        let x_one_away = self.x_score + 1 == self.length;
+
+        if !self.crawford_played && o_one_away != x_one_away {
+            self.crawford_played = true;
+            return GameRules {
+                crawford: true,
+                ..GameRules::default()
+            };
+        }
+
+        let trailer = match (o_one_away, x_one_away) {
+            (true, false) => Some(Side::X),
+            (false, true) => Some(Side::O),
+            _ => None,
+        };
+
+        GameRules {
+            auto_double: trailer.filter(|_| self.auto_double),
+            ..GameRules::default()
+        }
+    }
+}
+
+/// The result of one game of a money session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LedgerEntry {
+    pub game: u32,
+    pub winner: Side,
+    pub points: u32,
+    /// `o`'s net result after this game. `x`'s is the opposite.
+    pub o_balance: i64,
+}
+
+/// A money session: an open-ended series of games, all played with the same rules.
+pub struct MoneySession {
+    o_player: String,
+    x_player: String,
+    rules: GameRules,
+    games: Vec<Game>,
+    ledger: Vec<LedgerEntry>,
+    current: GameState,
+}
+
+impl MoneySession {
+    pub fn make(o_player: &str, x_player: &str, rules: GameRules) -> MoneySession {
+        MoneySession {
+            o_player: o_player.to_string(),
+            x_player: x_player.to_string(),
+            rules,
+            games: vec![],
+            ledger: vec![],
+            current: GameState::make(rules),
+        }
+    }
+
+    pub fn game(&self) -> &GameState {
+        &self.current
+    }
+
+    pub fn game_mut(&mut self) -> &mut GameState {
+        &mut self.current
+    }
+
+    pub fn ledger(&self) -> &[LedgerEntry] {
+        &self.ledger
+    }
+
+    /// The net result of the session so far for `side`.
+    pub fn balance(&self, side: Side) -> i64 {
+        let o_balance = self.ledger.last().map_or(0, |entry| entry.o_balance);
+        match side {
+            Side::O => o_balance,
+            Side::X => -o_balance,
+        }
+    }
+
+    /// Records the current game, which must be over, and starts the next one.
+    pub fn finish_game(&mut self) -> Result<(), PlayError> {
+        if !self.current.is_over() {
+            return Err(PlayError::OutOfTurn);
+        }
+
+        let state = std::mem::replace(&mut self.current, GameState::make(self.rules));
+        let mut game = state.into_game();
+        game.number = self.games.len() as u32 + 1;
+        game.o_player = self.o_player.clone();
+        game.x_player = self.x_player.clone();
+
+        // Scores in money sessions are the total points won by each player.
+        let total = |winner: Side| -> u32 {
+            self.ledger
+                .iter()
+                .filter(|entry| entry.winner == winner)
+                .map(|entry| entry.points)
+                .sum()
+        };
+        game.o_score = total(Side::O);
+        game.x_score = total(Side::X);
+
+        if let Some(winner) = game.winner {
+            let points = i64::from(game.points);
+            let o_balance = match winner {
+                Side::O => self.balance(Side::O) + points,
+                Side::X => self.balance(Side::O) - points,
+            };
+            self.ledger.push(LedgerEntry {
+                game: game.number,
+                winner,
+                points: game.points,
+                o_balance,
+            });
+        }
+
+        self.games.push(game);
+        Ok(())
+    }
+
+    /// The record of the games played so far, as a match of length 0.
+    pub fn into_match(self) -> Match {
+        Match::make(0, self.games)
+    }
+}
+
+/// 1 for a single game, 2 for a gammon and 3 for a backgammon.
+fn result_multiplier(position: &Position) -> u32 {
+    if position.o_has_backgammoned() || position.x_has_backgammoned() {
+        3
+    } else if position.o_has_gammoned() || position.x_has_gammoned() {
+        2
+    } else {
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_play_random_game() {
+        let mut state = GameState::make(GameRules::default());
+
+        assert_eq!(Ok(None), state.opening_roll(3, 3));
+        assert_eq!(Phase::Opening, state.phase());
+        assert_eq!(Ok(Some(Side::X)), state.opening_roll(2, 4));
+        assert_eq!(Phase::Moving(Side::X, Dice::make(4, 2)), state.phase());
+        assert_eq!(Err(PlayError::OutOfTurn), state.opening_roll(4, 2));
+
+        while !state.is_over() {
+            if let Phase::Rolling(_) | Phase::MustRoll(_) = state.phase() {
+                state.roll(Dice::roll()).unwrap();
+            }
+            let play = state.legal_plays()[0];
+            state.play(&play).unwrap();
+        }
+
+        let final_position = *state.position();
+        let game = state.into_game();
+        assert_eq!(Some(Side::X), game.starting_side);
+        assert_eq!(None, game.turns[0].0);
+        let last = game.replay().last().unwrap().unwrap();
+
+        assert_eq!(final_position, last.after);
+        assert_eq!(Some(last.side), game.winner);
+        assert!(game.points >= 1);
+    }
+
+    #[test]
+    fn test_cube_sequence() {
+        let mut state = GameState::make(GameRules::default());
+        assert_eq!(Err(PlayError::OutOfTurn), state.double());
+
+        state.opening_roll(1, 6).unwrap();
+        let play = state.legal_plays()[0];
+        state.play(&play).unwrap();
+
+        assert!(state.can_double());
+        state.double().unwrap();
+        assert_eq!(Phase::Doubled(Side::X), state.phase());
+        assert_eq!(Err(PlayError::OutOfTurn), state.roll(Dice::make(2, 1)));
+        state.take().unwrap();
+
+        // `x` now owns the cube, so `o` cannot redouble.
+        assert_eq!(Phase::MustRoll(Side::O), state.phase());
+        assert_eq!(Err(PlayError::CannotDouble), state.double());
+        state.roll(Dice::make(2, 1)).unwrap();
+        assert_eq!(
+            Err(PlayError::IllegalPlay),
+            state.play(&Position::initial())
+        );
+        let play = state.legal_plays()[0];
+        state.play(&play).unwrap();
+
+        state.double().unwrap();
+        state.drop().unwrap();
+        assert_eq!(
+            Phase::Over {
+                winner: Side::X,
+                points: 2
+            },
+            state.phase()
+        );
+
+        let game = state.into_game();
+        assert_eq!(Some(Side::X), game.winner);
+        assert_eq!(4, game.turns.len());
+        assert_eq!(Some(Action::Drop), game.turns[3].0);
+    }
+
+    #[test]
+    fn test_no_cube_in_crawford_game() {
+        let mut state = GameState::make(GameRules {
+            crawford: true,
+            ..GameRules::default()
+        });
+        let starter = state.roll_for_start().unwrap();
+        assert_eq!(Some(starter), state.game().starting_side);
+        let play = state.legal_plays()[0];
+        state.play(&play).unwrap();
+
+        assert_eq!(Phase::Rolling(starter.opponent()), state.phase());
+        assert!(!state.can_double());
+        assert_eq!(Err(PlayError::CannotDouble), state.double());
+    }
+
+    // The loser makes the opening play, then the winner doubles them out.
+    fn double_out(state: &mut GameState, winner: Side) {
+        if winner == Side::O {
+            state.opening_roll(1, 2).unwrap();
+        } else {
+            state.opening_roll(2, 1).unwrap();
+        }
+        let play = state.legal_plays()[0];
+        state.play(&play).unwrap();
+
+        if state.phase() == Phase::Rolling(winner) {
+            state.double().unwrap();
+        }
+        state.drop().unwrap();
+    }
+
+    // The play that leaves the player on roll with the lowest pip count. Ties are broken
+    // arbitrarily, but deterministically.
+    fn racing_play(state: &GameState) -> Position {
+        let side = match state.phase() {
+            Phase::Moving(side, _) => side,
+            _ => panic!("not moving"),
+        };
+
+        let pips = |p: &Position| {
+            let p = if side == Side::X { p.flip() } else { *p };
+            let on_board: u32 = (0..24)
+                .map(|i| u32::from(i + 1) * u32::from(p.point_o_value(i)))
+                .sum();
+            (
+                on_board + 25 * u32::from(p.o_bar_value()),
+                format!("{:?}", p),
+            )
+        };
+
+        state.legal_plays().into_iter().min_by_key(pips).unwrap()
+    }
+
+    // The winner always rolls 6-6, the loser 2-1.
+    fn race_out(state: &mut GameState, winner: Side) {
+        if winner == Side::O {
+            state.opening_roll(6, 5).unwrap();
+        } else {
+            state.opening_roll(5, 6).unwrap();
+        }
+
+        while !state.is_over() {
+            if let Phase::Rolling(side) | Phase::MustRoll(side) = state.phase() {
+                let dice = if side == winner {
+                    Dice::make(6, 6)
+                } else {
+                    Dice::make(2, 1)
+                };
+                state.roll(dice).unwrap();
+            }
+            let play = racing_play(state);
+            state.play(&play).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_match_crawford() {
+        let mut state = MatchState::make(5, "Alice", "Bob", true);
+
+        for _ in 0..4 {
+            double_out(state.game_mut().unwrap(), Side::O);
+            state.finish_game().unwrap();
+        }
+        assert!(state.game().unwrap().rules().crawford);
+        assert_eq!(MatchScore::make(1, 5, true), state.score(Side::O));
+        assert_eq!(Err(PlayError::OutOfTurn), state.finish_game());
+
+        race_out(state.game_mut().unwrap(), Side::X);
+        state.finish_game().unwrap();
+        assert!(state.x_score() > 0);
+        assert!(state.score(Side::X).is_post_crawford());
+        assert_eq!(
+            GameRules {
+                auto_double: Some(Side::X),
+                ..GameRules::default()
+            },
+            *state.game().unwrap().rules()
+        );
+
+        double_out(state.game_mut().unwrap(), Side::O);
+        state.finish_game().unwrap();
+        assert!(state.is_over());
+        assert!(state.game().is_none());
+
+        let m = state.into_match();
+        assert_eq!(6, m.games.len());
+        assert_eq!(Some(Side::O), m.winner());
+        let crawford: Vec<bool> = m.games.iter().map(|g| g.crawford).collect();
+        assert_eq!(vec![false, false, false, false, true, false], crawford);
+        assert_eq!("Bob", m.games[5].x_player);
+        assert_eq!(6, m.games[5].number);
+    }
+
+    #[test]
+    fn test_resignation() {
+        let mut state = GameState::make(GameRules::default());
+        state.opening_roll(2, 1).unwrap();
+        assert_eq!(Err(PlayError::OutOfTurn), state.accept());
+
+        state.resign(Resignation::Single).unwrap();
+        assert_eq!(Phase::Resigned(Side::X, Resignation::Single), state.phase());
+        state.reject().unwrap();
+        assert_eq!(Phase::Moving(Side::O, Dice::make(2, 1)), state.phase());
+
+        let play = state.legal_plays()[0];
+        state.play(&play).unwrap();
+        state.double().unwrap();
+        state.take().unwrap();
+        state.resign(Resignation::Gammon).unwrap();
+        state.accept().unwrap();
+
+        assert_eq!(
+            Phase::Over {
+                winner: Side::O,
+                points: 4
+            },
+            state.phase()
+        );
+        let game = state.into_game();
+        assert_eq!((Some(Action::Accept), None), *game.turns.last().unwrap());
+        assert_eq!(Some(Side::O), game.winner);
+    }
+
+    #[test]
+    fn test_beaver_and_raccoon() {
+        let mut state = GameState::make(GameRules::money());
+        state.opening_roll(2, 1).unwrap();
+        let play = state.legal_plays()[0];
+        state.play(&play).unwrap();
+
+        state.double().unwrap();
+        state.beaver().unwrap();
+        assert_eq!(Phase::Beavered(Side::X), state.phase());
+        assert_eq!((4, Some(Side::O)), (state.cube().value, state.cube().owner));
+
+        state.raccoon().unwrap();
+        assert_eq!(Phase::MustRoll(Side::X), state.phase());
+        assert_eq!((8, Some(Side::X)), (state.cube().value, state.cube().owner));
+        assert!(!state.can_double());
+        assert_eq!(Err(PlayError::CannotDouble), state.double());
+        assert_eq!(
+            (Some(Action::Beaver(4)), Some(Action::Beaver(8))),
+            state.game().turns[1]
+        );
+
+        let mut state = GameState::make(GameRules::default());
+        state.opening_roll(2, 1).unwrap();
+        let play = state.legal_plays()[0];
+        state.play(&play).unwrap();
+        state.double().unwrap();
+        assert_eq!(Err(PlayError::CannotDouble), state.beaver());
+    }
+
+    #[test]
+    fn test_jacoby_and_auto_doubles() {
+        let mut state = GameState::make(GameRules::money());
+        race_out(&mut state, Side::X);
+        assert_eq!(
+            Phase::Over {
+                winner: Side::X,
+                points: 1
+            },
+            state.phase()
+        );
+
+        let mut state = GameState::make(GameRules {
+            auto_doubles: 2,
+            ..GameRules::money()
+        });
+        for _ in 0..3 {
+            assert_eq!(Ok(None), state.opening_roll(4, 4));
+        }
+        assert_eq!(4, state.cube().value);
+        assert_eq!(None, state.cube().owner);
+
+        let mut state = GameState::make(GameRules {
+            auto_doubles: 40,
+            ..GameRules::money()
+        });
+        for _ in 0..40 {
+            assert_eq!(Ok(None), state.opening_roll(4, 4));
+        }
+        assert_eq!(1 << 31, state.cube().value);
+    }
+
+    #[test]
+    fn test_roll_sorts_dice() {
+        let dice = loop {
+            let dice = Dice::roll();
+            if dice.high() < dice.low() {
+                break dice;
+            }
+        };
+
+        let mut state = GameState::make(GameRules::default());
+        state.opening_roll(2, 1).unwrap();
+        let play = state.legal_plays()[0];
+        state.play(&play).unwrap();
+        state.roll(dice).unwrap();
+        assert_eq!(
+            Phase::Moving(Side::X, Dice::make(dice.low(), dice.high())),
+            state.phase()
+        );
+        if let Phase::Moving(_, sorted) = state.phase() {
+            assert!(sorted.high() > sorted.low());
+        }
+    }
+
+    #[test]
+    fn test_money_session() {
+        let mut session = MoneySession::make("Alice", "Bob", GameRules::money());
+
+        double_out(session.game_mut(), Side::X);
+        session.finish_game().unwrap();
+        race_out(session.game_mut(), Side::O);
+        session.finish_game().unwrap();
+        double_out(session.game_mut(), Side::X);
+        session.finish_game().unwrap();
+
+        let balances: Vec<i64> = session.ledger().iter().map(|e| e.o_balance).collect();
+        assert_eq!(vec![-1, 0, -1], balances);
+        assert_eq!(1, session.balance(Side::X));
+
+        let m = session.into_match();
+        assert_eq!(0, m.length);
+        assert_eq!((1, 1), (m.games[2].o_score, m.games[2].x_score));
+    }
+
+    #[test]
+    fn test_auto_double() {
+        let mut state = GameState::make(GameRules {
+            auto_double: Some(Side::X),
+            ..GameRules::default()
+        });
+        state.opening_roll(5, 2).unwrap();
+        let play = state.legal_plays()[0];
+        state.play(&play).unwrap();
+
+        assert_eq!(Phase::Doubled(Side::O), state.phase());
+        state.take().unwrap();
+
+        assert_eq!(Phase::MustRoll(Side::X), state.phase());
+        assert_eq!(Some(Side::O), state.cube().owner);
+    }
+}
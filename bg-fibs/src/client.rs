@@ -0,0 +1,330 @@
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use bg_core::eval::CubeOwnership;
+use bg_core::game::Resignation;
+use bg_core::met::MatchScore;
+use bg_core::movegen::{find_o_move, generate_o_moves};
+use bg_core::moves::Move;
+use bg_core::policy::{Policy, Situation};
+use bg_core::position::Side;
+use bg_parser::{parse_fibs_board, FibsBoard};
+
+// The CLIP version we speak, and how we introduce ourselves.
+const CLIP_VERSION: u32 = 1008;
+const CLIENT_NAME: &str = "bg-fibs";
+
+#[derive(Debug)]
+pub enum ClientError {
+    Io(io::Error),
+    /// The server rejected the name or password.
+    LoginFailed,
+    /// The server closed the connection.
+    Disconnected,
+    /// The server sent something we don't understand, or the policy played an illegal move.
+    Protocol(String),
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Io(e) => write!(f, "{}", e),
+            ClientError::LoginFailed => write!(f, "login failed"),
+            ClientError::Disconnected => write!(f, "disconnected"),
+            ClientError::Protocol(message) => write!(f, "protocol error: {}", message),
+        }
+    }
+}
+
+impl Error for ClientError {}
+
+impl From<io::Error> for ClientError {
+    fn from(e: io::Error) -> ClientError {
+        ClientError::Io(e)
+    }
+}
+
+/// How a match ended, as announced by the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MatchOutcome {
+    pub won: bool,
+    pub length: u32,
+    pub winner_score: u32,
+    pub loser_score: u32,
+}
+
+/// A client for FIBS-compatible servers, speaking the CLIP protocol. It logs in, sets up
+/// matches, and then plays them with a `Policy`.
+pub struct Client<P: Policy> {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    policy: P,
+    board: Option<FibsBoard>,
+    // The last board we acted on, so that it isn't acted on again if FIBS repeats it. It's
+    // forgotten once we send something, as the next board may well be the same.
+    acted_on: Option<String>,
+}
+
+impl<P: Policy> Client<P> {
+    /// Connects and logs in, and asks for boards as `board:...` strings.
+    pub fn connect<A: ToSocketAddrs>(
+        address: A,
+        name: &str,
+        password: &str,
+        policy: P,
+    ) -> Result<Client<P>, ClientError> {
+        let stream = TcpStream::connect(address)?;
+        stream.set_nodelay(true)?;
+        let mut client = Client {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+            policy,
+            board: None,
+            acted_on: None,
+        };
+
+        client.expect_login_prompt()?;
+        client.send(&format!(
+            "login {} {} {} {}",
+            CLIENT_NAME, CLIP_VERSION, name, password
+        ))?;
+
+        loop {
+            let line = client.read_line()?;
+            if line.starts_with("1 ") {
+                break;
+            }
+            if is_login_prompt(&line) {
+                return Err(ClientError::LoginFailed);
+            }
+        }
+
+        client.send("set boardstyle 3")?;
+        Ok(client)
+    }
+
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) -> Result<(), ClientError> {
+        Ok(self.writer.set_read_timeout(timeout)?)
+    }
+
+    /// The last board the server sent.
+    pub fn board(&self) -> Option<&FibsBoard> {
+        self.board.as_ref()
+    }
+
+    pub fn invite(&mut self, player: &str, length: u32) -> Result<(), ClientError> {
+        self.send(&format!("invite {} {}", player, length))
+    }
+
+    pub fn join(&mut self, player: &str) -> Result<(), ClientError> {
+        self.send(&format!("join {}", player))
+    }
+
+    /// Waits for someone to invite us, and returns who and for how many points.
+    pub fn wait_for_invitation(&mut self) -> Result<(String, u32), ClientError> {
+        loop {
+            let line = self.read_line()?;
+            if let Some(invitation) = parse_invitation(&line) {
+                return Ok(invitation);
+            }
+        }
+    }
+
+    /// Plays the match that was started with `invite` or `join` until the server announces
+    /// its end.
+    pub fn play_match(&mut self) -> Result<MatchOutcome, ClientError> {
+        loop {
+            let line = self.read_line()?;
+
+            if let Some(outcome) = parse_match_end(&line) {
+                return Ok(outcome);
+            } else if line.starts_with("board:") {
+                let board = parse_fibs_board(&line)
+                    .map_err(|e| ClientError::Protocol(format!("{}: {}", line, e)))?;
+                self.board = Some(board);
+                if self.acted_on.as_ref() != Some(&line) {
+                    self.acted_on = Some(line);
+                    self.act()?;
+                }
+            } else if line.ends_with("doubles. Type 'accept' or 'reject'.") {
+                let take = self.policy.take(&self.situation()?);
+                self.send(if take { "accept" } else { "reject" })?;
+            } else if let Some(points) = parse_resignation(&line) {
+                let situation = self.situation()?;
+                let resignation = Resignation::from_points(points / situation.cube_value.max(1))
+                    .ok_or_else(|| ClientError::Protocol(line.clone()))?;
+                let accept = self.policy.accept_resignation(&situation, resignation);
+                self.send(if accept { "accept" } else { "reject" })?;
+            }
+        }
+    }
+
+    /// Sends a command as is.
+    pub fn send(&mut self, command: &str) -> Result<(), ClientError> {
+        write!(self.writer, "{}\r\n", command)?;
+        self.acted_on = None;
+        Ok(self.writer.flush()?)
+    }
+
+    // Rolls, doubles or moves, if it's our turn.
+    fn act(&mut self) -> Result<(), ClientError> {
+        let board = self.current_board()?.clone();
+        if board.turn != Some(Side::O) || board.was_doubled {
+            return Ok(());
+        }
+
+        let situation = situation(&board);
+        let dice = match board.o_dice {
+            Some(dice) => dice,
+            None => {
+                let double = board.o_may_double && self.policy.double(&situation);
+                return self.send(if double { "double" } else { "roll" });
+            }
+        };
+
+        // The server plays for us when we cannot move.
+        if generate_o_moves(&board.position, &dice).is_empty() {
+            return Ok(());
+        }
+
+        let to = self.policy.play(&situation, &dice);
+        let m = find_o_move(&board.position, &dice, &to)
+            .ok_or_else(|| ClientError::Protocol(format!("illegal play {:?} with {}", to, dice)))?;
+        self.send(&format!("move {}", move_command(&board, &m)))
+    }
+
+    fn current_board(&self) -> Result<&FibsBoard, ClientError> {
+        self.board
+            .as_ref()
+            .ok_or_else(|| ClientError::Protocol("no board yet".to_string()))
+    }
+
+    fn situation(&self) -> Result<Situation, ClientError> {
+        Ok(situation(self.current_board()?))
+    }
+
+    fn expect_login_prompt(&mut self) -> Result<(), ClientError> {
+        loop {
+            if is_login_prompt(&self.read_line()?) {
+                return Ok(());
+            }
+        }
+    }
+
+    // The login prompt doesn't end with a newline, so it ends a line by itself.
+    fn read_line(&mut self) -> Result<String, ClientError> {
+        let mut line = vec![];
+        loop {
+            let mut byte = [0];
+            if self.reader.read(&mut byte)? == 0 {
+                if line.is_empty() {
+                    return Err(ClientError::Disconnected);
+                }
+                break;
+            }
+            if byte[0] == b'\n' {
+                break;
+            }
+            line.push(byte[0]);
+            if line.ends_with(b"login: ") {
+                break;
+            }
+        }
+
+        Ok(String::from_utf8_lossy(&line).trim_end().to_string())
+    }
+}
+
+fn is_login_prompt(line: &str) -> bool {
+    line.trim_end().ends_with("login:")
+}
+
+/// The situation on a board, for the player the board was sent to.
+pub fn situation(board: &FibsBoard) -> Situation {
+    let cube_owner = match (board.o_may_double, board.x_may_double) {
+        (true, false) => CubeOwnership::Player,
+        (false, true) => CubeOwnership::Opponent,
+        _ => CubeOwnership::Centered,
+    };
+
+    let score = if board.length == 0 {
+        None
+    } else {
+        let o_away = board.length.saturating_sub(board.o_score);
+        let x_away = board.length.saturating_sub(board.x_score);
+        // The Crawford game is the first one where exactly one player is 1 away.
+        let crawford = (o_away == 1) != (x_away == 1) && !board.did_crawford;
+        Some(MatchScore::make(o_away, x_away, crawford))
+    };
+
+    Situation {
+        position: board.position,
+        cube_value: board.cube,
+        cube_owner,
+        score,
+    }
+}
+
+// E.g. `bar-22 13-11`, with points numbered the way the board is.
+fn move_command(board: &FibsBoard, m: &Move) -> String {
+    let point = |p: u8| {
+        if board.direction == -1 {
+            p.to_string()
+        } else {
+            (25 - p).to_string()
+        }
+    };
+
+    m.moves()
+        .iter()
+        .map(|checker_move| {
+            let from = if checker_move.is_entering() {
+                "bar".to_string()
+            } else {
+                point(checker_move.src_point() + 1)
+            };
+            let to = if checker_move.is_bearing_off() {
+                "off".to_string()
+            } else {
+                point(checker_move.dst_point() + 1)
+            };
+            format!("{}-{}", from, to)
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// E.g. `alice wants to play a 5 point match with you.`
+fn parse_invitation(line: &str) -> Option<(String, u32)> {
+    let (player, rest) = line.split_once(" wants to play a ")?;
+    let length = rest.strip_suffix(" point match with you.")?.parse().ok()?;
+    Some((player.to_string(), length))
+}
+
+// E.g. `alice wants to resign. You will win 2 points. Type 'accept' or 'reject'.`
+fn parse_resignation(line: &str) -> Option<u32> {
+    let (_, rest) = line.split_once(" wants to resign. You will win ")?;
+    rest.split_whitespace().next()?.parse().ok()
+}
+
+// E.g. `You win the 5 point match 5-3 .` or `alice wins the 5 point match 5-1 .`
+fn parse_match_end(line: &str) -> Option<MatchOutcome> {
+    let (head, scores) = line.split_once(" point match ")?;
+    let (who, length) = head.rsplit_once(" the ")?;
+    let won = match who {
+        "You win" => true,
+        _ if who.ends_with(" wins") => false,
+        _ => return None,
+    };
+
+    let (winner_score, loser_score) = scores.trim_end_matches(&[' ', '.'][..]).split_once('-')?;
+    Some(MatchOutcome {
+        won,
+        length: length.parse().ok()?,
+        winner_score: winner_score.parse().ok()?,
+        loser_score: loser_score.parse().ok()?,
+    })
+}
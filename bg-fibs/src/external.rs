@@ -0,0 +1,84 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpStream;
+
+use bg_core::movegen::{find_o_move, generate_o_moves};
+use bg_core::policy::Policy;
+use bg_core::position::Side;
+use bg_parser::parse_fibs_board;
+
+use super::client::situation;
+
+/// Answers one request from GNUbg, which hands a decision to an external player by sending it
+/// the FIBS board string of the player to decide for.
+///
+/// The answer is `take` or `drop` when doubled, `double` or `roll` before rolling, and
+/// otherwise a move like `8/5 6/5`, with 25 for the bar and 0 for off. GNUbg's boards don't
+/// say when a player resigns, so resignations are left to GNUbg.
+pub fn answer<P: Policy>(policy: &mut P, request: &str) -> Result<String, String> {
+    let board =
+        parse_fibs_board(request).map_err(|e| format!("column {}: {}", e.column, e.message))?;
+    let situation = situation(&board);
+
+    if board.was_doubled {
+        let take = policy.take(&situation);
+        return Ok(if take { "take" } else { "drop" }.to_string());
+    }
+    if board.turn != Some(Side::O) {
+        return Err("not on roll".to_string());
+    }
+
+    let dice = match board.o_dice {
+        Some(dice) => dice,
+        None => {
+            let double = board.o_may_double && policy.double(&situation);
+            return Ok(if double { "double" } else { "roll" }.to_string());
+        }
+    };
+
+    if generate_o_moves(&board.position, &dice).is_empty() {
+        return Ok(String::new());
+    }
+    let to = policy.play(&situation, &dice);
+    let m = find_o_move(&board.position, &dice, &to)
+        .ok_or_else(|| format!("illegal play {:?} with {}", to, dice))?;
+
+    let moves: Vec<String> = m
+        .moves()
+        .iter()
+        .map(|checker_move| {
+            let from = if checker_move.is_entering() {
+                25
+            } else {
+                checker_move.src_point() + 1
+            };
+            let to = if checker_move.is_bearing_off() {
+                0
+            } else {
+                checker_move.dst_point() + 1
+            };
+            format!("{}/{}", from, to)
+        })
+        .collect();
+    Ok(moves.join(" "))
+}
+
+/// Answers GNUbg's requests on a connection until it's closed. Requests that can't be answered
+/// get an `error: ...` line, which GNUbg reports.
+pub fn serve_external<P: Policy>(stream: TcpStream, policy: &mut P) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    let reader = BufReader::new(stream);
+
+    for line in reader.lines() {
+        // GNUbg terminates its strings with a NUL, as well as a newline.
+        let line = line?;
+        let request = line.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+        if request.is_empty() {
+            continue;
+        }
+
+        let response = answer(policy, request).unwrap_or_else(|e| format!("error: {}", e));
+        writeln!(writer, "{}", response)?;
+        writer.flush()?;
+    }
+    Ok(())
+}
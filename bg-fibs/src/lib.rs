@@ -0,0 +1,294 @@
+mod client;
+mod external;
+mod mock;
+
+pub use client::{situation, Client, ClientError, MatchOutcome};
+pub use external::{answer, serve_external};
+pub use mock::{MockConfig, MockServer};
+
+#[cfg(test)]
+use bg_core::dice::Dice;
+#[cfg(test)]
+use bg_core::game::{Action, Match};
+#[cfg(test)]
+use bg_core::movegen::generate_o_moves;
+#[cfg(test)]
+use bg_core::policy::{Policy, Situation};
+#[cfg(test)]
+use bg_core::position::{Position, Side};
+#[cfg(test)]
+use bg_parser::FibsBoard;
+#[cfg(test)]
+use std::io::{BufRead, BufReader, Write};
+#[cfg(test)]
+use std::net::{TcpListener, TcpStream};
+#[cfg(test)]
+use std::thread;
+#[cfg(test)]
+use std::time::Duration;
+
+// Plays the first legal move it finds, and makes the same cube decisions every time.
+#[cfg(test)]
+struct FixedPolicy {
+    doubles: bool,
+    takes: bool,
+}
+
+#[cfg(test)]
+impl Policy for FixedPolicy {
+    fn play(&mut self, situation: &Situation, dice: &Dice) -> Position {
+        *generate_o_moves(&situation.position, dice)
+            .iter()
+            .next()
+            .unwrap()
+    }
+
+    fn double(&mut self, _situation: &Situation) -> bool {
+        self.doubles
+    }
+
+    fn take(&mut self, _situation: &Situation) -> bool {
+        self.takes
+    }
+}
+
+#[cfg(test)]
+fn connect(server: &MockServer, policy: FixedPolicy) -> Client<FixedPolicy> {
+    let mut client = Client::connect(server.address(), "alice", "secret", policy).unwrap();
+    client
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    client
+}
+
+#[cfg(test)]
+fn has_action(m: &Match, side: Side, action: fn(&Action) -> bool) -> bool {
+    m.games.iter().flat_map(|g| g.turns.iter()).any(|turn| {
+        let a = if side == Side::O { &turn.0 } else { &turn.1 };
+        a.as_ref().is_some_and(action)
+    })
+}
+
+#[test]
+fn test_login_failure() {
+    let server = MockServer::start(MockConfig::default()).unwrap();
+    let policy = FixedPolicy {
+        doubles: false,
+        takes: true,
+    };
+    let result = Client::connect(server.address(), "alice", "wrong", policy);
+
+    assert!(matches!(result, Err(ClientError::LoginFailed)));
+    drop(result);
+    assert!(server.finish().is_err());
+}
+
+#[test]
+fn test_join_and_take() {
+    let config = MockConfig {
+        doubles: true,
+        ..MockConfig::default()
+    };
+    let server = MockServer::start(config).unwrap();
+    let mut client = connect(
+        &server,
+        FixedPolicy {
+            doubles: false,
+            takes: true,
+        },
+    );
+
+    let (player, length) = client.wait_for_invitation().unwrap();
+    assert_eq!(("mock".to_string(), 3), (player, length));
+    client.join("mock").unwrap();
+    let outcome = client.play_match().unwrap();
+    let m = server.finish().unwrap();
+
+    assert_eq!(3, outcome.length);
+    assert!(outcome.winner_score >= 3);
+    assert_eq!(outcome.won, m.winner() == Some(Side::O));
+    assert!(has_action(&m, Side::X, |a| matches!(a, Action::Double(_))));
+    assert!(has_action(&m, Side::O, |a| *a == Action::Take));
+}
+
+#[test]
+fn test_drop_every_double() {
+    let config = MockConfig {
+        doubles: true,
+        ..MockConfig::default()
+    };
+    let server = MockServer::start(config).unwrap();
+    let mut client = connect(
+        &server,
+        FixedPolicy {
+            doubles: false,
+            takes: false,
+        },
+    );
+
+    client.wait_for_invitation().unwrap();
+    client.join("mock").unwrap();
+    let outcome = client.play_match().unwrap();
+    let m = server.finish().unwrap();
+
+    assert_eq!(outcome.won, m.winner() == Some(Side::O));
+    assert!(has_action(&m, Side::O, |a| *a == Action::Drop));
+    assert!(!has_action(&m, Side::O, |a| *a == Action::Take));
+}
+
+#[test]
+fn test_invite_and_double() {
+    let config = MockConfig {
+        invites: false,
+        takes: false,
+        direction: 1,
+        ..MockConfig::default()
+    };
+    let server = MockServer::start(config).unwrap();
+    let mut client = connect(
+        &server,
+        FixedPolicy {
+            doubles: true,
+            takes: true,
+        },
+    );
+
+    client.invite("mock", 5).unwrap();
+    let outcome = client.play_match().unwrap();
+    let m = server.finish().unwrap();
+
+    // The client wins every game but the Crawford game by doubling, and `mock` can't win 5
+    // points in that one.
+    assert!(outcome.won);
+    assert_eq!(5, outcome.length);
+    assert!(outcome.winner_score >= 5);
+    assert_eq!(Some(Side::O), m.winner());
+    assert!(has_action(&m, Side::O, |a| matches!(a, Action::Double(_))));
+    assert!(has_action(&m, Side::X, |a| *a == Action::Drop));
+}
+
+#[test]
+fn test_external_answer() {
+    let mut policy = FixedPolicy {
+        doubles: true,
+        takes: false,
+    };
+    let position = Position::make(&[(1, 2)], &[(24, 15)], 0, 0, 13, 0);
+    let mut board = FibsBoard::make("You", "gnubg", position);
+    board.turn = Some(Side::O);
+
+    assert_eq!(
+        Ok("double".to_string()),
+        answer(&mut policy, &board.to_string())
+    );
+    board.o_may_double = false;
+    assert_eq!(
+        Ok("roll".to_string()),
+        answer(&mut policy, &board.to_string())
+    );
+
+    board.o_dice = Some(Dice::make(2, 1));
+    assert_eq!(
+        Ok("1/0 1/0".to_string()),
+        answer(&mut policy, &board.to_string())
+    );
+
+    board.o_dice = None;
+    board.turn = Some(Side::X);
+    board.was_doubled = true;
+    assert_eq!(
+        Ok("drop".to_string()),
+        answer(&mut policy, &board.to_string())
+    );
+
+    board.was_doubled = false;
+    assert!(answer(&mut policy, &board.to_string()).is_err());
+    assert!(answer(&mut policy, "board:You:gnubg").is_err());
+}
+
+#[test]
+fn test_situation_crawford() {
+    let mut board = FibsBoard::make("You", "gnubg", Position::initial());
+    board.length = 3;
+    board.x_score = 2;
+    assert!(situation(&board).score.unwrap().crawford);
+
+    board.did_crawford = true;
+    assert!(!situation(&board).score.unwrap().crawford);
+
+    // Neither may double at 1-away 1-away either, but it's after the Crawford game.
+    board.o_score = 2;
+    board.did_crawford = false;
+    board.o_may_double = false;
+    board.x_may_double = false;
+    assert!(!situation(&board).score.unwrap().crawford);
+}
+
+#[test]
+fn test_repeated_board() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut lines = BufReader::new(stream.try_clone().unwrap()).lines();
+        write!(stream, "login: ").unwrap();
+        lines.next().unwrap().unwrap();
+        write!(stream, "1 alice 1041253132 192.168.1.308\r\n").unwrap();
+        lines.next().unwrap().unwrap();
+
+        // The same board again after our command must be answered again.
+        let mut board = FibsBoard::make("You", "gnubg", Position::initial());
+        board.length = 1;
+        board.turn = Some(Side::O);
+        let mut commands = vec![];
+        for _ in 0..2 {
+            write!(stream, "{}\r\n", board).unwrap();
+            commands.push(lines.next().unwrap().unwrap());
+        }
+        write!(stream, "You win the 1 point match 1-0 .\r\n").unwrap();
+        commands
+    });
+
+    let mut client = Client::connect(
+        address,
+        "alice",
+        "secret",
+        FixedPolicy {
+            doubles: false,
+            takes: true,
+        },
+    )
+    .unwrap();
+    client
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    assert!(client.play_match().unwrap().won);
+    assert_eq!(vec!["roll", "roll"], server.join().unwrap());
+}
+
+#[test]
+fn test_serve_external() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut policy = FixedPolicy {
+            doubles: false,
+            takes: true,
+        };
+        serve_external(stream, &mut policy).unwrap();
+    });
+
+    let mut board = FibsBoard::make("You", "gnubg", Position::initial());
+    board.turn = Some(Side::X);
+    board.was_doubled = true;
+
+    let mut stream = TcpStream::connect(address).unwrap();
+    write!(stream, "{}\n\0\nunknown\n\0", board).unwrap();
+    stream.shutdown(std::net::Shutdown::Write).unwrap();
+    let lines: Vec<String> = BufReader::new(stream).lines().map(|l| l.unwrap()).collect();
+    server.join().unwrap();
+
+    assert_eq!("take", lines[0]);
+    assert_eq!(vec!["take", "error: column 1: expected `board`"], lines);
+}
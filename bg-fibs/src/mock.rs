@@ -0,0 +1,372 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use bg_core::dice::Dice;
+use bg_core::game::Match;
+use bg_core::moves::{CheckerMove, Move};
+use bg_core::position::{Position, Side};
+use bg_core::state::{MatchState, Phase};
+use bg_parser::FibsBoard;
+
+// How long the server waits for the client before giving up.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How the mock server and its player behave.
+#[derive(Debug, Clone)]
+pub struct MockConfig {
+    /// The name of the server's player.
+    pub name: String,
+    /// The password the client must log in with. Any name is accepted.
+    pub password: String,
+    /// The match length when the server invites the client.
+    pub length: u32,
+    /// Whether the server invites the client, rather than waiting for an invitation.
+    pub invites: bool,
+    /// Whether the server's player doubles whenever the cube is centered and available.
+    pub doubles: bool,
+    /// Whether the server's player takes doubles.
+    pub takes: bool,
+    /// The direction of the boards sent to the client: -1 or 1.
+    pub direction: i32,
+}
+
+impl Default for MockConfig {
+    fn default() -> MockConfig {
+        MockConfig {
+            name: "mock".to_string(),
+            password: "secret".to_string(),
+            length: 3,
+            invites: true,
+            doubles: false,
+            takes: true,
+            direction: -1,
+        }
+    }
+}
+
+/// A local server that speaks enough CLIP to play one match with one client, to test clients
+/// offline. Its player makes racing plays with random dice.
+pub struct MockServer {
+    address: SocketAddr,
+    handle: JoinHandle<io::Result<Match>>,
+}
+
+impl MockServer {
+    /// Listens on a free local port, and serves the first client that connects.
+    pub fn start(config: MockConfig) -> io::Result<MockServer> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let address = listener.local_addr()?;
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept()?;
+            Session::make(stream, config)?.run()
+        });
+
+        Ok(MockServer { address, handle })
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Waits for the session to end, and returns the record of the match, with the client as
+    /// `o`.
+    pub fn finish(self) -> io::Result<Match> {
+        self.handle
+            .join()
+            .unwrap_or_else(|_| Err(io::Error::other("server panicked")))
+    }
+}
+
+struct Session {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    config: MockConfig,
+    user: String,
+}
+
+impl Session {
+    fn make(stream: TcpStream, config: MockConfig) -> io::Result<Session> {
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_nodelay(true)?;
+        Ok(Session {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+            config,
+            user: String::new(),
+        })
+    }
+
+    fn run(mut self) -> io::Result<Match> {
+        self.login()?;
+        let length = self.start_match()?;
+        self.play_match(length)
+    }
+
+    fn login(&mut self) -> io::Result<()> {
+        loop {
+            write!(self.writer, "login: ")?;
+            self.writer.flush()?;
+
+            let line = self.read_line()?;
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if let ["login", _, _, name, password] = words[..] {
+                if password == self.config.password {
+                    self.user = name.to_string();
+                    break;
+                }
+            }
+        }
+
+        let user = self.user.clone();
+        self.send(&format!("1 {} 1041253132 localhost", user))?;
+        self.send(&format!(
+            "2 {} 1 1 0 0 0 0 1 1 0 0 1 0 1 1500.00 0 0 0 0 0 UTC",
+            user
+        ))?;
+        self.send("3")?;
+        self.send("Welcome to the mock server.")?;
+        self.send("4")
+    }
+
+    // Invites the client, or waits for its invitation, and returns the match length.
+    fn start_match(&mut self) -> io::Result<u32> {
+        let name = self.config.name.clone();
+        if self.config.invites {
+            let length = self.config.length;
+            self.send(&format!(
+                "{} wants to play a {} point match with you.",
+                name, length
+            ))?;
+            self.send(&format!("Type 'join {}' to accept.", name))?;
+            while self.read_line()? != format!("join {}", name) {}
+            self.send(&format!(
+                "** You are now playing a {} point match with {}",
+                length, name
+            ))?;
+            return Ok(length);
+        }
+
+        loop {
+            let line = self.read_line()?;
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if let ["invite", player, length] = words[..] {
+                if let (true, Ok(length)) = (player == name, length.parse()) {
+                    self.send(&format!(
+                        "** Player {} has joined you for a {} point match.",
+                        name, length
+                    ))?;
+                    return Ok(length);
+                }
+            }
+        }
+    }
+
+    fn play_match(&mut self, length: u32) -> io::Result<Match> {
+        let name = self.config.name.clone();
+        let mut state = MatchState::make(length, &self.user, &name, false);
+
+        while let Some(game) = state.game_mut() {
+            if game.phase() == Phase::Opening {
+                self.send(&format!("Starting a new game with {}.", name))?;
+                game.roll_for_start().map_err(invalid)?;
+                self.send_board(&state)?;
+            }
+            self.step(&mut state)?;
+        }
+
+        let (o, x) = (state.o_score(), state.x_score());
+        match state.winner() {
+            Some(Side::O) => {
+                self.send(&format!("You win the {} point match {}-{} .", length, o, x))?
+            }
+            _ => self.send(&format!(
+                "{} wins the {} point match {}-{} .",
+                name, length, x, o
+            ))?,
+        }
+        Ok(state.into_match())
+    }
+
+    // Advances the current game by one action.
+    fn step(&mut self, state: &mut MatchState) -> io::Result<()> {
+        let name = self.config.name.clone();
+        let game = state.game_mut().unwrap();
+
+        match game.phase() {
+            Phase::Over { winner, points } => {
+                let point_word = if points == 1 { "point" } else { "points" };
+                match winner {
+                    Side::O => self.send(&format!(
+                        "You win the game and get {} {}.",
+                        points, point_word
+                    ))?,
+                    Side::X => self.send(&format!(
+                        "{} wins the game and gets {} {}.",
+                        name, points, point_word
+                    ))?,
+                }
+                state.finish_game().map_err(invalid)?;
+            }
+            Phase::Rolling(Side::O) | Phase::MustRoll(Side::O) => {
+                match self.read_line()?.as_str() {
+                    "roll" => {
+                        game.roll(Dice::roll()).map_err(invalid)?;
+                        self.send_board(state)?;
+                    }
+                    "double" if game.can_double() => {
+                        game.double().map_err(invalid)?;
+                        self.send(&format!(
+                            "You double. Please wait for {} to accept or reject.",
+                            name
+                        ))?;
+                    }
+                    "double" => self.send("** You can't double.")?,
+                    _ => (),
+                }
+            }
+            Phase::Rolling(Side::X) | Phase::MustRoll(Side::X) => {
+                if self.config.doubles && game.cube().owner.is_none() && game.can_double() {
+                    game.double().map_err(invalid)?;
+                    self.send(&format!("{} doubles. Type 'accept' or 'reject'.", name))?;
+                } else {
+                    game.roll(Dice::roll()).map_err(invalid)?;
+                }
+            }
+            Phase::Moving(side, dice) => {
+                let plays = game.legal_plays();
+                let to = if plays == [*game.position()] {
+                    plays[0]
+                } else if side == Side::X {
+                    racing_play(&plays)
+                } else {
+                    let line = self.read_line()?;
+                    match self.parse_move(game.position(), &dice, &line) {
+                        Some(to) if plays.contains(&to) => to,
+                        _ => return self.send("** You can't move that way."),
+                    }
+                };
+                game.play(&to).map_err(invalid)?;
+                if !game.is_over() {
+                    self.send_board(state)?;
+                }
+            }
+            Phase::Doubled(Side::O) => match self.read_line()?.as_str() {
+                "accept" => {
+                    game.take().map_err(invalid)?;
+                    let value = game.cube().value;
+                    self.send(&format!("You accept the double. The cube shows {}.", value))?;
+                    self.send_board(state)?;
+                }
+                "reject" => game.drop().map_err(invalid)?,
+                _ => (),
+            },
+            Phase::Doubled(Side::X) => {
+                if self.config.takes {
+                    game.take().map_err(invalid)?;
+                    let value = game.cube().value;
+                    self.send(&format!(
+                        "{} accepts the double. The cube shows {}.",
+                        name, value
+                    ))?;
+                    self.send_board(state)?;
+                } else {
+                    game.drop().map_err(invalid)?;
+                    self.send(&format!("{} refuses the double.", name))?;
+                }
+            }
+            phase => return Err(invalid(format!("unexpected phase {:?}", phase))),
+        }
+        Ok(())
+    }
+
+    // E.g. `move bar-22 13-11`, with points numbered the way the boards are.
+    fn parse_move(&self, position: &Position, dice: &Dice, line: &str) -> Option<Position> {
+        let point = |s: &str, edge: &str, edge_point: u8| -> Option<u8> {
+            if s == edge {
+                return Some(edge_point);
+            }
+            let p: u8 = s.parse().ok().filter(|p| (1..=24).contains(p))?;
+            Some(if self.config.direction == -1 {
+                p
+            } else {
+                25 - p
+            })
+        };
+
+        let mut words = line.split_whitespace();
+        if words.next() != Some("move") {
+            return None;
+        }
+        let mut checker_moves = vec![];
+        for word in words {
+            let (from, to) = word.split_once('-')?;
+            let (from, to) = (point(from, "bar", 25)?, point(to, "off", 0)?);
+            checker_moves.push(CheckerMove::make(from, to, false));
+        }
+        position.try_apply_o_move(&Move::make(*dice, checker_moves))
+    }
+
+    fn send_board(&mut self, state: &MatchState) -> io::Result<()> {
+        let game = state.game().unwrap();
+        let mut board = FibsBoard::make("You", &self.config.name, *game.position());
+        board.length = state.length();
+        board.o_score = state.o_score();
+        board.x_score = state.x_score();
+        board.direction = self.config.direction;
+        board.cube = game.cube().value;
+        board.o_may_double = !game.rules().crawford && game.cube().owner != Some(Side::X);
+        board.x_may_double = !game.rules().crawford && game.cube().owner != Some(Side::O);
+        let one_away = |score: u32| score + 1 == state.length();
+        board.did_crawford =
+            (one_away(board.o_score) || one_away(board.x_score)) && !game.rules().crawford;
+
+        match game.phase() {
+            Phase::Rolling(side) | Phase::MustRoll(side) => board.turn = Some(side),
+            Phase::Moving(side, dice) => {
+                board.turn = Some(side);
+                match side {
+                    Side::O => board.o_dice = Some(dice),
+                    Side::X => board.x_dice = Some(dice),
+                }
+            }
+            // The doubler is still on roll.
+            Phase::Doubled(side) => {
+                board.turn = Some(side.opponent());
+                board.was_doubled = side == Side::O;
+            }
+            _ => (),
+        }
+
+        self.send(&board.to_string())
+    }
+
+    fn send(&mut self, line: &str) -> io::Result<()> {
+        write!(self.writer, "{}\r\n", line)?;
+        self.writer.flush()
+    }
+
+    fn read_line(&mut self) -> io::Result<String> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(line.trim().to_string())
+    }
+}
+
+// The play that leaves `x` with the lowest pip count.
+fn racing_play(plays: &[Position]) -> Position {
+    let pips = |p: &Position| {
+        let points: u32 = (0..24u8)
+            .map(|i| u32::from(p.point_x_value(i)) * (24 - u32::from(i)))
+            .sum();
+        points + 25 * u32::from(p.x_bar_value())
+    };
+    *plays.iter().min_by_key(|p| pips(p)).unwrap()
+}
+
+fn invalid<E: ToString>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
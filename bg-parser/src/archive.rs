@@ -0,0 +1,252 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use bg_core::game::Match;
+use regex::Regex;
+
+use super::error::ParseError;
+use super::parse_match;
+use super::sgf::parse_sgf;
+
+// The extensions of the files read when walking a directory.
+const MAT_EXTENSIONS: [&str; 3] = ["mat", "txt", "bg"];
+const SGF_EXTENSION: &str = "sgf";
+
+/// Why a match in an archive couldn't be read.
+#[derive(Debug)]
+pub enum ArchiveError {
+    Io(io::Error),
+    Parse(ParseError),
+}
+
+impl fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArchiveError::Io(e) => write!(f, "{}", e),
+            ArchiveError::Parse(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for ArchiveError {}
+
+/// A match read from an archive, or the error that prevented reading it.
+#[derive(Debug)]
+pub struct ArchiveEntry {
+    pub path: PathBuf,
+    /// The 1-based line where the match starts in its file.
+    pub line: usize,
+    pub result: Result<Match, ArchiveError>,
+}
+
+/// Reads the matches one after the other from `.mat` contents that may hold several of them,
+/// each starting with its metadata or its `N point match` header.
+pub struct MatchReader<R> {
+    path: PathBuf,
+    lines: io::Lines<R>,
+    line: usize,
+    chunk: Vec<String>,
+    // The 0-based line of the first line of the chunk.
+    chunk_start: usize,
+    has_header: bool,
+    done: bool,
+    header: Regex,
+}
+
+impl<R: BufRead> MatchReader<R> {
+    /// `path` only labels the entries.
+    pub fn make(path: PathBuf, reader: R) -> MatchReader<R> {
+        MatchReader {
+            path,
+            lines: reader.lines(),
+            line: 0,
+            chunk: vec![],
+            chunk_start: 0,
+            has_header: false,
+            done: false,
+            header: Regex::new(r"^\s*[1-9][0-9]* point match\s*$").unwrap(),
+        }
+    }
+
+    fn entry(&self, chunk: Vec<String>, line: usize) -> ArchiveEntry {
+        let result = parse_match(&chunk.join("\n")).map_err(|mut e| {
+            e.line += line;
+            ArchiveError::Parse(e)
+        });
+
+        ArchiveEntry {
+            path: self.path.clone(),
+            line: line + 1,
+            result,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for MatchReader<R> {
+    type Item = ArchiveEntry;
+
+    fn next(&mut self) -> Option<ArchiveEntry> {
+        while !self.done {
+            let line = match self.lines.next() {
+                Some(Ok(line)) => line,
+                Some(Err(e)) => {
+                    self.done = true;
+                    return Some(ArchiveEntry {
+                        path: self.path.clone(),
+                        line: self.line + 1,
+                        result: Err(ArchiveError::Io(e)),
+                    });
+                }
+                None => {
+                    self.done = true;
+                    if self.chunk.iter().all(|l| l.trim().is_empty()) {
+                        return None;
+                    }
+                    let chunk = std::mem::take(&mut self.chunk);
+                    return Some(self.entry(chunk, self.chunk_start));
+                }
+            };
+
+            if !self.header.is_match(&line) {
+                self.line += 1;
+                self.chunk.push(line);
+                continue;
+            }
+
+            // The metadata right before a header belongs to the next match.
+            let mut split = self.chunk.len();
+            while split > 0 && is_metadata_or_blank(&self.chunk[split - 1]) {
+                split -= 1;
+            }
+            while split < self.chunk.len() && self.chunk[split].trim().is_empty() {
+                split += 1;
+            }
+            let next = self.chunk.split_off(split);
+            let previous = std::mem::replace(&mut self.chunk, next);
+            let previous_start = self.chunk_start;
+            self.chunk_start += split;
+            let had_header = std::mem::replace(&mut self.has_header, true);
+
+            let entry = if had_header || previous.iter().any(|l| !l.trim().is_empty()) {
+                Some(self.entry(previous, previous_start))
+            } else {
+                None
+            };
+
+            self.line += 1;
+            self.chunk.push(line);
+            if entry.is_some() {
+                return entry;
+            }
+        }
+
+        None
+    }
+}
+
+fn is_metadata_or_blank(line: &str) -> bool {
+    let line = line.trim();
+    line.is_empty() || line.starts_with(';')
+}
+
+/// Reads every match in a file, or in the `.mat`, `.txt`, `.bg` and `.sgf` files of a directory
+/// tree. Files are read one at a time, and errors are returned as entries so that the matches
+/// after them can still be read.
+pub fn read_archive<P: AsRef<Path>>(path: P) -> Archive {
+    Archive {
+        pending: vec![path.as_ref().to_path_buf()],
+        current: None,
+    }
+}
+
+/// The matches of an archive, in file order, with the files of a directory sorted by name.
+pub struct Archive {
+    // A stack of the files and directories still to read.
+    pending: Vec<PathBuf>,
+    current: Option<MatchReader<BufReader<File>>>,
+}
+
+impl Archive {
+    fn open(&mut self, path: PathBuf) -> Option<ArchiveEntry> {
+        let error = |path: PathBuf, e: io::Error| ArchiveEntry {
+            path,
+            line: 1,
+            result: Err(ArchiveError::Io(e)),
+        };
+
+        if path.is_dir() {
+            let entries = match fs::read_dir(&path) {
+                Ok(entries) => entries,
+                Err(e) => return Some(error(path, e)),
+            };
+            let mut children = vec![];
+            for entry in entries {
+                match entry {
+                    Ok(entry) => {
+                        let child = entry.path();
+                        if child.is_dir() || has_extension(&child) {
+                            children.push(child);
+                        }
+                    }
+                    Err(e) => return Some(error(path, e)),
+                }
+            }
+            children.sort();
+            children.reverse();
+            self.pending.append(&mut children);
+            return None;
+        }
+
+        if is_sgf(&path) {
+            let result = fs::read_to_string(&path)
+                .map_err(ArchiveError::Io)
+                .and_then(|contents| parse_sgf(&contents).map_err(ArchiveError::Parse));
+            return Some(ArchiveEntry {
+                path,
+                line: 1,
+                result,
+            });
+        }
+
+        match File::open(&path) {
+            Ok(file) => {
+                self.current = Some(MatchReader::make(path, BufReader::new(file)));
+                None
+            }
+            Err(e) => Some(error(path, e)),
+        }
+    }
+}
+
+impl Iterator for Archive {
+    type Item = ArchiveEntry;
+
+    fn next(&mut self) -> Option<ArchiveEntry> {
+        loop {
+            if let Some(entry) = self.current.as_mut().and_then(|reader| reader.next()) {
+                return Some(entry);
+            }
+            self.current = None;
+
+            let path = self.pending.pop()?;
+            if let Some(entry) = self.open(path) {
+                return Some(entry);
+            }
+        }
+    }
+}
+
+fn has_extension(path: &Path) -> bool {
+    is_sgf(path)
+        || path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| MAT_EXTENSIONS.contains(&e))
+}
+
+fn is_sgf(path: &Path) -> bool {
+    path.extension().and_then(|e| e.to_str()) == Some(SGF_EXTENSION)
+}
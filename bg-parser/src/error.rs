@@ -0,0 +1,110 @@
+use std::error::Error;
+use std::fmt;
+
+use lalrpop_util::lexer::Token;
+
+type LalrpopError<'a> = lalrpop_util::ParseError<usize, Token<'a>, (usize, &'static str)>;
+
+/// Why a match couldn't be parsed, and where.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// 1-based line and column of the problem.
+    pub line: usize,
+    pub column: usize,
+    /// The offending token, or `None` at the end of the input or on an invalid token.
+    pub token: Option<String>,
+    /// The tokens the grammar would have accepted instead, as named in the grammar.
+    pub expected: Vec<String>,
+    pub message: String,
+    /// The whole offending line, to point at the problem.
+    pub source_line: String,
+}
+
+impl ParseError {
+    pub(crate) fn make(input: &str, error: LalrpopError<'_>) -> ParseError {
+        let (location, token, expected, message) = match error {
+            lalrpop_util::ParseError::InvalidToken { location } => {
+                (location, None, vec![], "invalid token".to_string())
+            }
+            lalrpop_util::ParseError::UnrecognizedEOF { location, expected } => (
+                location,
+                None,
+                expected,
+                "unexpected end of input".to_string(),
+            ),
+            lalrpop_util::ParseError::UnrecognizedToken {
+                token: (start, token, _),
+                expected,
+            } => (
+                start,
+                Some(token.to_string()),
+                expected,
+                format!("unexpected token `{}`", token),
+            ),
+            lalrpop_util::ParseError::ExtraToken {
+                token: (start, token, _),
+            } => (
+                start,
+                Some(token.to_string()),
+                vec![],
+                format!("extra token `{}`", token),
+            ),
+            lalrpop_util::ParseError::User {
+                error: (location, message),
+            } => (location, None, vec![], message.to_string()),
+        };
+
+        ParseError {
+            token,
+            expected,
+            ..ParseError::at(input, location, message)
+        }
+    }
+
+    /// An error at the given byte offset in the input.
+    pub(crate) fn at(input: &str, location: usize, message: String) -> ParseError {
+        let location = location.min(input.len());
+        let line_start = input[..location].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = input[location..]
+            .find('\n')
+            .map_or(input.len(), |i| location + i);
+
+        ParseError {
+            line: input[..location].matches('\n').count() + 1,
+            column: input[line_start..location].chars().count() + 1,
+            token: None,
+            expected: vec![],
+            message,
+            source_line: input[line_start..line_end].trim_end().to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )?;
+        if !self.expected.is_empty() {
+            write!(f, ", expected one of {}", self.expected.join(", "))?;
+        }
+
+        // Tabs are kept so that the caret lines up with them.
+        let margin = " ".repeat(self.line.to_string().len());
+        let padding: String = self
+            .source_line
+            .chars()
+            .chain(std::iter::repeat(' '))
+            .take(self.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+
+        writeln!(f)?;
+        writeln!(f, "{} | {}", self.line, self.source_line)?;
+        write!(f, "{} | {}^", margin, padding)
+    }
+}
+
+impl Error for ParseError {}
@@ -0,0 +1,282 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use bg_core::dice::Dice;
+use bg_core::position::{Position, Side};
+
+use super::error::ParseError;
+
+// `board`, the names, the match length and scores, 26 board fields and 21 more.
+const FIELDS: usize = 53;
+
+// FIBS' match length for unlimited (money) matches.
+const UNLIMITED: u32 = 9999;
+
+/// A FIBS `board:...` string: a position, with the dice, the cube and the score.
+///
+/// FIBS describes the board from the point of view of the player it's sent to, "You", who is
+/// `o` here. Its own colours don't match ours: You may play either FIBS colour, and in either
+/// direction, which only matter when writing the string back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FibsBoard {
+    pub o_player: String,
+    pub x_player: String,
+    /// The match length, or 0 for unlimited matches.
+    pub length: u32,
+    pub o_score: u32,
+    pub x_score: u32,
+    pub position: Position,
+    /// The side on roll, or `None` when the game is over.
+    pub turn: Option<Side>,
+    pub o_dice: Option<Dice>,
+    pub x_dice: Option<Dice>,
+    pub cube: u32,
+    pub o_may_double: bool,
+    pub x_may_double: bool,
+    /// Whether `x` has doubled and `o` must answer.
+    pub was_doubled: bool,
+    /// `o`'s FIBS colour: 1 for O, whose checkers are positive on the board, or -1 for X.
+    pub color: i32,
+    /// -1 if `o` moves towards the board's 0 end, 1 towards its 25 end.
+    pub direction: i32,
+    /// How many checkers `o` can move with its dice.
+    pub can_move: u32,
+    pub forced_move: bool,
+    /// Whether the Crawford game has already been played.
+    pub did_crawford: bool,
+    pub redoubles: u32,
+}
+
+impl FibsBoard {
+    /// A board with the given position and FIBS' usual colour and direction for `o`, before
+    /// anyone rolls.
+    pub fn make(o_player: &str, x_player: &str, position: Position) -> FibsBoard {
+        FibsBoard {
+            o_player: o_player.to_string(),
+            x_player: x_player.to_string(),
+            length: 0,
+            o_score: 0,
+            x_score: 0,
+            position,
+            turn: None,
+            o_dice: None,
+            x_dice: None,
+            cube: 1,
+            o_may_double: true,
+            x_may_double: true,
+            was_doubled: false,
+            color: 1,
+            direction: -1,
+            can_move: 0,
+            forced_move: false,
+            did_crawford: false,
+            redoubles: 0,
+        }
+    }
+
+    // The index on the FIBS board of `o`'s point (1 to 24, or 0 for `o`'s bar).
+    fn index(&self, point: usize) -> usize {
+        match (self.direction, point) {
+            (-1, 0) => 25,
+            (-1, _) => point,
+            (_, 0) => 0,
+            _ => 25 - point,
+        }
+    }
+}
+
+/// Reads a FIBS `board:...` string.
+pub fn parse_fibs_board(input: &str) -> Result<FibsBoard, ParseError> {
+    let input = input.trim_end();
+    let mut offsets = vec![0];
+    offsets.extend(input.match_indices(':').map(|(i, _)| i + 1));
+    let fields: Vec<&str> = input.split(':').collect();
+
+    let error = |field: usize, message: String| {
+        ParseError::at(input, offsets[field.min(offsets.len() - 1)], message)
+    };
+
+    if fields[0] != "board" {
+        return Err(error(0, "expected `board`".to_string()));
+    }
+    if fields.len() != FIELDS {
+        return Err(error(
+            fields.len().min(FIELDS),
+            format!("expected {} fields, not {}", FIELDS, fields.len()),
+        ));
+    }
+
+    let int = |field: usize| -> Result<i32, ParseError> {
+        fields[field]
+            .parse()
+            .map_err(|_| error(field, format!("invalid number `{}`", fields[field])))
+    };
+    let count = |field: usize| -> Result<u32, ParseError> {
+        u32::try_from(int(field)?).map_err(|_| error(field, "expected a count".to_string()))
+    };
+    let flag = |field: usize| -> Result<bool, ParseError> {
+        match int(field)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(error(field, "expected 0 or 1".to_string())),
+        }
+    };
+    let sign = |field: usize| -> Result<i32, ParseError> {
+        match int(field)? {
+            value @ (-1 | 1) => Ok(value),
+            _ => Err(error(field, "expected -1 or 1".to_string())),
+        }
+    };
+    // A point holds at most 15 checkers, signed by their owner.
+    let checkers = |field: usize| -> Result<i32, ParseError> {
+        match int(field)? {
+            value @ -15..=15 => Ok(value),
+            _ => Err(error(field, "expected at most 15 checkers".to_string())),
+        }
+    };
+    let checker_count = |field: usize| -> Result<u8, ParseError> {
+        u8::try_from(count(field)?)
+            .ok()
+            .filter(|&n| n <= 15)
+            .ok_or_else(|| error(field, "expected at most 15 checkers".to_string()))
+    };
+    let dice = |field: usize| -> Result<Option<Dice>, ParseError> {
+        match (int(field)?, int(field + 1)?) {
+            (0, 0) => Ok(None),
+            (first @ 1..=6, second @ 1..=6) => Ok(Some(Dice::make(first as u8, second as u8))),
+            _ => Err(error(field, "invalid dice".to_string())),
+        }
+    };
+
+    let color = sign(41)?;
+    let direction = sign(42)?;
+    let length = count(3)?;
+
+    let mut board = FibsBoard {
+        o_player: fields[1].to_string(),
+        x_player: fields[2].to_string(),
+        length: if length == UNLIMITED { 0 } else { length },
+        o_score: count(4)?,
+        x_score: count(5)?,
+        position: Position::initial(),
+        turn: match int(32)? {
+            0 => None,
+            turn if turn == color => Some(Side::O),
+            turn if turn == -color => Some(Side::X),
+            _ => return Err(error(32, "expected -1, 0 or 1".to_string())),
+        },
+        o_dice: dice(33)?,
+        x_dice: dice(35)?,
+        cube: count(37)?,
+        o_may_double: flag(38)?,
+        x_may_double: flag(39)?,
+        was_doubled: flag(40)?,
+        color,
+        direction,
+        can_move: count(49)?,
+        forced_move: flag(50)?,
+        did_crawford: flag(51)?,
+        redoubles: count(52)?,
+    };
+
+    let home = if direction == -1 { 0 } else { 25 };
+    if (int(43)?, int(44)?) != (home, 25 - home) {
+        return Err(error(
+            43,
+            "home and bar don't match the direction".to_string(),
+        ));
+    }
+
+    let (mut o_points, mut x_points) = (vec![], vec![]);
+    for point in 1..=24 {
+        let value = checkers(6 + board.index(point))? * color;
+        let n = u8::try_from(value.abs()).unwrap();
+        if value > 0 {
+            o_points.push((point as u8, n));
+        } else if value < 0 {
+            x_points.push((point as u8, n));
+        }
+    }
+
+    let counts = [
+        checker_count(45)?,
+        checker_count(46)?,
+        checker_count(47)?,
+        checker_count(48)?,
+    ];
+    let total = |points: &[(u8, u8)], home: u8, bar: u8| {
+        points.iter().map(|(_, n)| u32::from(*n)).sum::<u32>() + u32::from(home) + u32::from(bar)
+    };
+    if total(&o_points, counts[0], counts[2]) != 15 || total(&x_points, counts[1], counts[3]) != 15
+    {
+        return Err(error(6, "each side must have 15 checkers".to_string()));
+    }
+
+    board.position = Position::make(
+        &o_points, &x_points, counts[2], counts[3], counts[0], counts[1],
+    );
+    Ok(board)
+}
+
+impl fmt::Display for FibsBoard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let length = if self.length == 0 {
+            UNLIMITED
+        } else {
+            self.length
+        };
+        write!(
+            f,
+            "board:{}:{}:{}:{}:{}",
+            self.o_player, self.x_player, length, self.o_score, self.x_score
+        )?;
+
+        let p = &self.position;
+        let mut points = [0; 26];
+        for point in 1..=24 {
+            let o = i32::from(p.point_o_value(point as u8 - 1));
+            let x = i32::from(p.point_x_value(point as u8 - 1));
+            points[self.index(point)] = (o - x) * self.color;
+        }
+        points[self.index(0)] = i32::from(p.o_bar_value()) * self.color;
+        points[25 - self.index(0)] = -i32::from(p.x_bar_value()) * self.color;
+        for value in points.iter() {
+            write!(f, ":{}", value)?;
+        }
+
+        let turn = match self.turn {
+            Some(Side::O) => self.color,
+            Some(Side::X) => -self.color,
+            None => 0,
+        };
+        let dice = |dice: Option<Dice>| dice.map_or((0, 0), |d| (d.high(), d.low()));
+        let (o_dice, x_dice) = (dice(self.o_dice), dice(self.x_dice));
+        let home = if self.direction == -1 { 0 } else { 25 };
+
+        write!(
+            f,
+            ":{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}",
+            turn,
+            o_dice.0,
+            o_dice.1,
+            x_dice.0,
+            x_dice.1,
+            self.cube,
+            u8::from(self.o_may_double),
+            u8::from(self.x_may_double),
+            u8::from(self.was_doubled),
+            self.color,
+            self.direction,
+            home,
+            25 - home,
+            p.o_home_value(),
+            p.x_home_value(),
+            p.o_bar_value(),
+            p.x_bar_value(),
+            self.can_move,
+            u8::from(self.forced_move),
+            u8::from(self.did_crawford),
+            self.redoubles
+        )
+    }
+}
@@ -3,20 +3,40 @@ extern crate lalrpop_util;
 
 lalrpop_mod!(#[allow(clippy::all)] pub bg_game);
 
+mod archive;
+mod error;
+mod fibs;
+mod sgf;
+mod validate;
+mod writer;
+
+pub use archive::{read_archive, Archive, ArchiveEntry, ArchiveError, MatchReader};
+pub use error::ParseError;
+pub use fibs::{parse_fibs_board, FibsBoard};
+pub use sgf::{parse_sgf, write_sgf};
+pub use validate::{validate, Diagnostic};
+pub use writer::{write_game, write_match};
+
 use bg_core::game::Match;
 
 // Public entrypoint.
-pub fn parse_match(str: &str) -> Option<Match> {
+pub fn parse_match(str: &str) -> Result<Match, ParseError> {
     let parser = bg_game::MatchParser::new();
-    parser.parse(str).ok()
+    parser.parse(str).map_err(|e| ParseError::make(str, e))
 }
 
 #[cfg(test)]
 use bg_core::dice::Dice;
 #[cfg(test)]
+use bg_core::game::{Action, Resignation};
+#[cfg(test)]
 use bg_core::moves::CheckerMove;
 #[cfg(test)]
 use bg_core::moves::Move;
+#[cfg(test)]
+use bg_core::position::{Position, Side};
+#[cfg(test)]
+use bg_core::state::{GameRules, GameState};
 
 #[test]
 fn test_parse_player_identifier() {
@@ -70,6 +90,50 @@ fn test_parse_roll() {
     assert_eq!(Dice::make(4, 3), roll_parser.parse("43:").unwrap());
 }
 
+#[test]
+fn test_parse_cube_action() {
+    let cube_action_parser = bg_game::CubeActionParser::new();
+    assert_eq!(
+        Action::Double(2),
+        cube_action_parser.parse("Doubles => 2").unwrap()
+    );
+    assert_eq!(Action::Take, cube_action_parser.parse("Takes").unwrap());
+    assert_eq!(Action::Drop, cube_action_parser.parse("Drops").unwrap());
+    assert_eq!(
+        Action::Beaver(4),
+        cube_action_parser.parse("Beavers => 4").unwrap()
+    );
+    assert!(cube_action_parser.parse("Doubles => 0").is_err());
+}
+
+#[test]
+fn test_parse_resign_action() {
+    let resign_action_parser = bg_game::ResignActionParser::new();
+    assert_eq!(
+        Action::Resign(Resignation::Gammon),
+        resign_action_parser.parse("Resigns 2 points").unwrap()
+    );
+    assert_eq!(
+        Action::Resign(Resignation::Single),
+        resign_action_parser.parse("Resigns 1").unwrap()
+    );
+    assert_eq!(Action::Accept, resign_action_parser.parse("Accepts").unwrap());
+    assert!(resign_action_parser.parse("Resigns 4 points").is_err());
+
+    let game_parser = bg_game::GameParser::new();
+    let game = game_parser
+        .parse(
+            "Game 1\nAlice : 0\tBob : 0\n\
+             1) 31: 8/5 6/5\tResigns 1\n\
+             2) Rejects\t61: 13/7 8/7\n\
+             3) Resigns 3 points\tAccepts\n\
+             \t\tWins 3 points",
+        )
+        .unwrap();
+    assert_eq!(Some(Side::X), game.winner);
+    assert!(game.replay().all(|step| step.is_ok()));
+}
+
 #[test]
 fn test_parse_checker_move() {
     let move_parser = bg_game::CheckerMoveParser::new();
@@ -125,4 +189,464 @@ fn test_parse_turn() {
         .parse("5) 21: 13/11 11/10\t\tDoubles => 2")
         .is_ok());
     assert!(turn_parser.parse("2) Doubles => 2\t\tTakes").is_ok());
+
+    assert_eq!(
+        (Some(Action::Double(2)), Some(Action::Drop)),
+        turn_parser.parse("9) Doubles => 2\t\tDrops").unwrap()
+    );
+    assert_eq!(
+        (None, Some(Action::Move(Move::make(Dice::make(2, 1), vec![])))),
+        turn_parser.parse("1)\t\t21:").unwrap()
+    );
+    assert_eq!(
+        (None, Some(Action::Double(2))),
+        turn_parser.parse("12)                                  Doubles => 2").unwrap()
+    );
+    assert_eq!((Some(Action::Drop), None), turn_parser.parse("13)  Drops").unwrap());
+}
+
+#[test]
+fn test_parse_game_header_fields() {
+    let game_header_parser = bg_game::GameHeaderParser::new();
+    assert_eq!(
+        (3, "Heike".to_string(), 1, "psuter".to_string(), 2),
+        game_header_parser
+            .parse("Game 3\nHeike : 1\t\t\tpsuter : 2")
+            .unwrap()
+    );
+}
+
+#[test]
+fn test_parse_full_match() {
+    let contents = std::fs::read_to_string("../data/game.bg").unwrap();
+    let m = parse_match(&contents).unwrap();
+
+    assert_eq!(5, m.length);
+    let first = &m.games[0];
+    assert_eq!(Some(Side::X), first.starting_side);
+    assert_eq!("Heike", first.o_player);
+    assert_eq!("psuter", first.x_player);
+    assert_eq!(Some(Side::X), first.winner);
+    assert_eq!(2, first.points);
+
+    let last = m.games.last().unwrap();
+    assert_eq!(2, last.points);
+    assert_eq!(m.winner(), last.winner);
+    assert!(m.winner().is_some());
+}
+
+#[test]
+fn test_replay_full_match() {
+    let contents = std::fs::read_to_string("../data/game.bg").unwrap();
+    let m = parse_match(&contents).unwrap();
+
+    for game in &m.games {
+        assert!(game.replay().all(|step| step.is_ok()));
+    }
+}
+
+#[test]
+fn test_parse_error() {
+    let error = parse_match("3 point match\n\nGame 1\nAlice : 0\tBob : 0\n 1) 31: 8/5 6/5 Foo\n")
+        .unwrap_err();
+
+    assert_eq!((5, 17), (error.line, error.column));
+    assert_eq!(Some("Foo".to_string()), error.token);
+    assert!(error.expected.contains(&"\"Takes\"".to_string()));
+    assert!(error
+        .to_string()
+        .ends_with("5 |  1) 31: 8/5 6/5 Foo\n  |                 ^"));
+
+    let error = parse_match("3 point match\n\nGame 1\nAlice : 0\tBob : 0\n 1) Resigns 5\n Wins 1 point")
+        .unwrap_err();
+    assert_eq!((5, 5), (error.line, error.column));
+    assert_eq!("invalid resignation", error.message);
+}
+
+#[test]
+fn test_validate() {
+    // The sample leaves out the last moves of the games that weren't decided by the cube.
+    let contents = std::fs::read_to_string("../data/game.bg").unwrap();
+    let m = parse_match(&contents).unwrap();
+    let messages: Vec<String> = validate(&m).iter().map(|d| d.to_string()).collect();
+    assert_eq!(
+        vec![
+            "game 1: the game ends without a result",
+            "game 3: the game ends without a result",
+            "game 5: the game ends without a result",
+            "game 6: the game ends without a result",
+        ],
+        messages
+    );
+
+    let m = parse_match(
+        "3 point match\n\n\
+         Game 1\nAlice : 0\tBob : 0\n\
+         1) 31: 8/5* 6/5\t64: 24/18 13/9\n\
+         2) 52: 13/11 11/8\tDoubles => 2\n\
+         3) Drops\tWins 2 points\n\n\
+         Game 2\nAlice : 0\tBob : 0\n\
+         1) 21: 13/11 6/5\t11: 6/5\n\
+         2) Resigns 1\tWins 1 point\n\
+         and the match",
+    )
+    .unwrap();
+
+    let messages: Vec<String> = validate(&m).iter().map(|d| d.to_string()).collect();
+    assert_eq!(
+        vec![
+            "game 1, turn 1: 8/5* should not be marked as a hit",
+            "game 1, turn 2: o plays 5-2: 13/11 11/8, which is not legal",
+            "game 1: the game is worth 1 points, not 2",
+            "game 2, turn 1: x plays 1-1: 6/5, which is not legal",
+            "game 2: the score should be 0-2 after the previous game",
+        ],
+        messages
+    );
+}
+
+#[test]
+fn test_round_trip() {
+    let contents = std::fs::read_to_string("../data/game.bg").unwrap();
+    let m = parse_match(&contents).unwrap();
+
+    let written = write_match(&m);
+    let reparsed = parse_match(&written).unwrap();
+    assert_eq!(m, reparsed);
+    assert_eq!(written, write_match(&reparsed));
+
+    let lines: Vec<&str> = written.lines().collect();
+    assert_eq!(" 5 point match", lines[0]);
+    assert_eq!(" Game 1", lines[2]);
+    assert_eq!(
+        " Heike : 0                           psuter : 0",
+        lines[3]
+    );
+    assert_eq!("  1)                                 41: 24/23 13/9", lines[4]);
+    assert_eq!(
+        " 24) 61: 25/19 3/2                    Doubles => 2",
+        lines[27]
+    );
+    assert_eq!(" 25)  Takes                          33: 11/8 8/5 3/0 3/0", lines[28]);
+    assert!(written.ends_with("Wins 2 points and the match\n"));
+}
+
+#[cfg(test)]
+fn played_game(number: u32, x_score: u32, state: GameState) -> bg_core::game::Game {
+    bg_core::game::Game {
+        number,
+        o_player: "Bartholomew-Alexander-Montgomery".to_string(),
+        x_player: "Bob".to_string(),
+        x_score,
+        ..state.into_game()
+    }
+}
+
+#[test]
+fn test_round_trip_money_session() {
+    // `x` doubles and `o` drops.
+    let mut first = GameState::make(GameRules::money());
+    first.opening_roll(3, 1).unwrap();
+    let play = first.legal_plays()[0];
+    first.play(&play).unwrap();
+    first.double().unwrap();
+    first.drop().unwrap();
+
+    // `o` beavers and `x` raccoons, so `x` moves on a turn of their own. The game isn't over.
+    let mut second = GameState::make(GameRules::money());
+    second.opening_roll(3, 1).unwrap();
+    let play = second.legal_plays()[0];
+    second.play(&play).unwrap();
+    second.double().unwrap();
+    second.beaver().unwrap();
+    second.raccoon().unwrap();
+    second.roll(Dice::make(6, 5)).unwrap();
+    let play = second.legal_plays()[0];
+    second.play(&play).unwrap();
+    assert!(!second.is_over());
+
+    let m = Match::make(0, vec![played_game(1, 0, first), played_game(2, 1, second)]);
+    assert_eq!((Some(Action::Beaver(4)), Some(Action::Beaver(8))), m.games[1].turns[1]);
+    assert!(m.games[1].turns[2].0.is_none());
+
+    let written = write_match(&m);
+    assert!(!written.contains("point match"));
+    assert!(!written.contains("and the match"));
+    assert_eq!(1, written.matches("Wins").count());
+    assert!(written.contains(" Bartholomew-Alexander-Montgomery : 0 Bob : 0\n"));
+    assert_eq!(m, parse_match(&written).unwrap());
+
+    // A match that isn't over yet has a header, but no footer.
+    let m = Match::make(3, m.games);
+    let written = write_match(&m);
+    assert!(written.starts_with(" 3 point match\n"));
+    assert!(!written.contains("and the match"));
+    assert_eq!(m, parse_match(&written).unwrap());
+}
+
+#[test]
+fn test_sgf_round_trip() {
+    let contents = std::fs::read_to_string("../data/game.bg").unwrap();
+    let m = parse_match(&contents).unwrap();
+
+    let sgf = write_sgf(&m);
+    assert!(sgf.starts_with(
+        "(;FF[4]GM[6]CA[UTF-8]MI[length:5][game:0][ws:0][bs:0]PB[Heike]PW[psuter]RU[Crawford]RE[W+2]\n;W[41xwmi]\n;B[64hbfb]\n"
+    ));
+    assert_eq!(m, parse_sgf(&sgf).unwrap());
+}
+
+#[test]
+fn test_parse_sgf() {
+    let m = parse_sgf(
+        "(;FF[4]GM[6]AP[GNU Backgammon:1.06.002]MI[length:3][game:1][ws:1][bs:0]\
+         PW[Bob]PB[Alice]RU[Crawford:CrawfordGame]RE[B+1R]\
+         ;B[31hefe]C[A comment \\] with a bracket]\
+         ;W[64xrmi]DA[analysis]\
+         (;B[resign:1];W[accept])(;B[double]))",
+    )
+    .unwrap();
+
+    let game = &m.games[0];
+    assert_eq!((3, 2), (m.length, game.number));
+    assert_eq!(
+        ("Alice", 0, "Bob", 1),
+        (
+            game.o_player.as_str(),
+            game.o_score,
+            game.x_player.as_str(),
+            game.x_score
+        )
+    );
+    assert!(game.crawford);
+    assert_eq!((Some(Side::O), 1), (game.winner, game.points));
+    assert_eq!(
+        vec![
+            (
+                Some(Action::Move(Move::make(
+                    Dice::make(3, 1),
+                    vec![
+                        CheckerMove::make(8, 5, false),
+                        CheckerMove::make(6, 5, false)
+                    ]
+                ))),
+                Some(Action::Move(Move::make(
+                    Dice::make(6, 4),
+                    vec![
+                        CheckerMove::make(24, 18, false),
+                        CheckerMove::make(13, 9, false)
+                    ]
+                )))
+            ),
+            (
+                Some(Action::Resign(Resignation::Single)),
+                Some(Action::Accept)
+            ),
+        ],
+        game.turns
+    );
+
+    let error = parse_sgf("(;FF[4]GM[6]\n;B[31hefq])").unwrap_err();
+    assert_eq!((2, 4), (error.line, error.column));
+    assert_eq!("invalid action `31hefq`", error.message);
+
+    // There's no checker on the 5-point to move.
+    let error = parse_sgf("(;FF[4]GM[6]\n;B[31ebdc])").unwrap_err();
+    assert_eq!((2, 4), (error.line, error.column));
+    assert_eq!("invalid action `31ebdc`", error.message);
+}
+
+#[test]
+fn test_parse_metadata() {
+    let contents = std::fs::read_to_string("../data/game.bg").unwrap();
+    let contents = format!(
+        "; [Site \"FIBS\"]\n; [Player 1 \"Heike\"]\n;[EventDate \"2021.03.14\"]\n\
+         ; [Annotator \"A \\\"quoted\\\" name\"]\n\n{}",
+        contents
+    );
+    let m = parse_match(&contents).unwrap();
+
+    assert_eq!(Some("FIBS"), m.metadata("Site"));
+    assert_eq!(Some("Heike"), m.metadata("Player 1"));
+    assert_eq!(Some("2021.03.14"), m.metadata("EventDate"));
+    assert_eq!(Some("A \"quoted\" name"), m.metadata("Annotator"));
+    assert_eq!(None, m.metadata("Round"));
+
+    let written = write_match(&m);
+    assert!(written.starts_with("; [Site \"FIBS\"]\n; [Player 1 \"Heike\"]\n"));
+    assert_eq!(m, parse_match(&written).unwrap());
+}
+
+#[test]
+fn test_parse_player_score() {
+    let game_header_parser = bg_game::GameHeaderParser::new();
+    assert_eq!(
+        (1, "Mary Ann".to_string(), 2, "Jörg_2.0".to_string(), 0),
+        game_header_parser.parse("Game 1\nMary Ann : 2\tJörg_2.0:0").unwrap()
+    );
+    assert_eq!(
+        (1, "2pac".to_string(), 1, "1alice".to_string(), 0),
+        game_header_parser.parse("Game 1\n2pac : 1 1alice : 0").unwrap()
+    );
+    assert!(game_header_parser.parse("Game 1\n123 : 1 1alice : 0").is_err());
+    assert_eq!(
+        (2, "Dr:Who".to_string(), 0, "Player 7".to_string(), 1),
+        game_header_parser.parse("Game 2\nDr:Who : 0\tPlayer 7 : 1").unwrap()
+    );
+    assert_eq!(
+        (2, "R2 D2".to_string(), 10, "a:1".to_string(), 3),
+        game_header_parser.parse("Game 2\nR2 D2 : 10 a:1:3").unwrap()
+    );
+    assert!(game_header_parser.parse("Game 1 2pac : 1 1alice : 0").is_err());
+    assert!(bg_game::PlayerIdentifierParser::new().parse("1alice").is_ok());
+
+    // Names and scores are only read in game headers.
+    let turn_parser = bg_game::TurnParser::new();
+    assert_eq!(
+        (
+            Some(Action::Take),
+            Some(Action::Move(Move::make(
+                Dice::make(3, 3),
+                vec![CheckerMove::make(13, 10, false), CheckerMove::make(13, 10, false)]
+            )))
+        ),
+        turn_parser.parse("3) Takes 33: 13/10 13/10").unwrap()
+    );
+
+    let game_parser = bg_game::GameParser::new();
+    let game = game_parser
+        .parse("Game 2\nAlice : 0\tBob : 2 (Crawford)\n1) 31: 8/5 6/5\tResigns 1\n2) Accepts\nWins 1 point")
+        .unwrap();
+    assert!(game.crawford);
+}
+
+#[test]
+fn test_parse_corpus() {
+    let mut paths: Vec<_> = std::fs::read_dir("../data/corpus")
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    paths.sort();
+    assert_eq!(5, paths.len());
+
+    for path in paths {
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let m = parse_match(&contents).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        assert!(validate(&m).is_empty(), "{}", path.display());
+        assert!(m.winner().is_some(), "{}", path.display());
+        assert_eq!(m, parse_match(&write_match(&m)).unwrap());
+
+        let name = path.file_name().unwrap().to_str().unwrap();
+        match name {
+            "colons.mat" => {
+                assert_eq!("Player 7", m.games[1].o_player);
+                assert_eq!(1, m.games[1].o_score);
+                assert_eq!("Dr:Who 2", m.games[1].x_player);
+                assert_eq!(0, m.games[1].x_score);
+            }
+            "crawford.mat" => {
+                let crawford: Vec<bool> = m.games.iter().map(|g| g.crawford).collect();
+                assert_eq!(vec![false, true, false], crawford);
+            }
+            "digits.mat" => {
+                assert_eq!("1alice", m.games[0].o_player);
+                assert_eq!("Bob2", m.games[0].x_player);
+                assert_eq!(Some(Action::Take), m.games[0].turns[2].0);
+            }
+            "gnubg_export.mat" => {
+                assert_eq!(Some("j.doe-72"), m.metadata("Player 1"));
+                assert_eq!("_ann", m.games[0].x_player);
+            }
+            "names.mat" => {
+                assert_eq!("Jörg_2.0", m.games[1].o_player);
+                assert_eq!("Mary Ann", m.games[1].x_player);
+            }
+            _ => panic!("unexpected file {}", name),
+        }
+    }
+}
+
+#[test]
+fn test_read_archive() {
+    let entries: Vec<ArchiveEntry> = read_archive("../data/archive").collect();
+    let locations: Vec<(String, usize)> = entries
+        .iter()
+        .map(|e| (e.path.display().to_string(), e.line))
+        .collect();
+    assert_eq!(
+        vec![
+            ("../data/archive/dump.mat".to_string(), 1),
+            ("../data/archive/dump.mat".to_string(), 18),
+            ("../data/archive/dump.mat".to_string(), 24),
+            ("../data/archive/sgf/crawford.sgf".to_string(), 1),
+        ],
+        locations
+    );
+
+    assert_eq!(
+        "Mary Ann",
+        entries[0].result.as_ref().unwrap().games[0].x_player
+    );
+    match &entries[1].result {
+        Err(ArchiveError::Parse(e)) => assert_eq!((22, 18), (e.line, e.column)),
+        other => panic!("expected a parse error, got {:?}", other),
+    }
+    assert_eq!(
+        Some("FIBS"),
+        entries[2].result.as_ref().unwrap().metadata("Site")
+    );
+    assert_eq!(3, entries[3].result.as_ref().unwrap().games.len());
+}
+
+#[test]
+fn test_fibs_board() {
+    // The example from the FIBS client protocol documentation.
+    let example = "board:You:someplayer:3:0:0:\
+                   0:-2:0:0:0:0:5:0:3:0:0:0:-5:5:0:0:0:-3:0:-5:0:0:0:0:2:0:\
+                   1:6:2:0:0:1:1:1:0:1:-1:0:25:0:0:0:0:2:0:0:0";
+    let board = parse_fibs_board(example).unwrap();
+    assert_eq!(Position::initial(), board.position);
+    assert_eq!(
+        ("You", "someplayer"),
+        (board.o_player.as_str(), board.x_player.as_str())
+    );
+    assert_eq!((3, Some(Side::O)), (board.length, board.turn));
+    assert_eq!((Some(Dice::make(6, 2)), None), (board.o_dice, board.x_dice));
+    assert_eq!(example, board.to_string());
+
+    // The same position, with You playing X from the other end of the board.
+    let mirrored = "board:You:someplayer:9999:0:0:\
+                    0:-2:0:0:0:0:5:0:3:0:0:0:-5:5:0:0:0:-3:0:-5:0:0:0:0:2:0:\
+                    1:0:0:5:5:1:1:1:0:-1:1:25:0:0:0:0:0:0:0:0:0";
+    let board = parse_fibs_board(mirrored).unwrap();
+    assert_eq!(Position::initial(), board.position);
+    assert_eq!((0, Some(Side::X)), (board.length, board.turn));
+    assert_eq!(Some(Dice::make(5, 5)), board.x_dice);
+    assert_eq!(mirrored, board.to_string());
+
+    let position = Position::make(&[(1, 12), (2, 1)], &[(24, 14)], 1, 0, 1, 1);
+    let mut board = FibsBoard::make("alice", "bob", position);
+    board.turn = Some(Side::X);
+    assert_eq!(board, parse_fibs_board(&board.to_string()).unwrap());
+    board.color = -1;
+    board.direction = 1;
+    assert_eq!(
+        "board:alice:bob:9999:0:0:\
+         -1:14:0:0:0:0:0:0:0:0:0:0:0:0:0:0:0:0:0:0:0:0:0:-1:-12:0:\
+         1:0:0:0:0:1:1:1:0:-1:1:25:0:1:1:1:0:0:0:0:0",
+        board.to_string()
+    );
+    assert_eq!(board, parse_fibs_board(&board.to_string()).unwrap());
+
+    let error = parse_fibs_board("board:You:someplayer:3:0:0:1").unwrap_err();
+    assert_eq!("expected 53 fields, not 7", error.message);
+    let error = parse_fibs_board(&example.replace(":-5:0:0:0:0:2:", ":-5:0:0:0:0:3:")).unwrap_err();
+    assert_eq!("each side must have 15 checkers", error.message);
+    for value in &["16", "-16", "-2147483648"] {
+        let field = format!(":-5:0:0:0:0:{}:", value);
+        let error = parse_fibs_board(&example.replace(":-5:0:0:0:0:2:", &field)).unwrap_err();
+        assert_eq!("expected at most 15 checkers", error.message);
+    }
+    let error = parse_fibs_board(&example.replace(":25:0:0:0:0:2:", ":25:256:0:0:0:2:")).unwrap_err();
+    assert_eq!("expected at most 15 checkers", error.message);
 }
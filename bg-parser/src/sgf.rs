@@ -0,0 +1,411 @@
+use std::fmt::Write;
+
+use bg_core::dice::Dice;
+use bg_core::game::{Action, Cube, Game, Match, Resignation};
+use bg_core::moves::{CheckerMove, Move};
+use bg_core::position::{Position, Side};
+
+use super::error::ParseError;
+
+// GNUbg's SGF files hold one game tree per game. The root node has the players (`o` is black,
+// `x` is white), the match information and the result, and each following node one action.
+
+struct Property {
+    name: String,
+    values: Vec<(usize, String)>,
+}
+
+type Node = Vec<Property>;
+
+struct Reader<'a> {
+    input: &'a str,
+    location: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn peek(&mut self) -> Option<char> {
+        let rest = &self.input[self.location..];
+        let trimmed = rest.trim_start();
+        self.location += rest.len() - trimmed.len();
+        trimmed.chars().next()
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), ParseError> {
+        if self.peek() == Some(c) {
+            self.location += 1;
+            Ok(())
+        } else {
+            Err(self.error(format!("expected `{}`", c)))
+        }
+    }
+
+    fn error(&self, message: String) -> ParseError {
+        ParseError::at(self.input, self.location, message)
+    }
+
+    fn collection(&mut self) -> Result<Vec<Vec<Node>>, ParseError> {
+        let mut trees = vec![];
+        while self.peek().is_some() {
+            trees.push(self.tree()?);
+        }
+        Ok(trees)
+    }
+
+    // Only the main line of a tree is kept, variations are skipped.
+    fn tree(&mut self) -> Result<Vec<Node>, ParseError> {
+        self.expect('(')?;
+        let mut nodes = vec![];
+        while self.peek() == Some(';') {
+            self.location += 1;
+            nodes.push(self.node()?);
+        }
+
+        let mut variation = 0;
+        while self.peek() == Some('(') {
+            let mut main_line = self.tree()?;
+            if variation == 0 {
+                nodes.append(&mut main_line);
+            }
+            variation += 1;
+        }
+
+        self.expect(')')?;
+        Ok(nodes)
+    }
+
+    fn node(&mut self) -> Result<Node, ParseError> {
+        let mut node = vec![];
+        while let Some(c) = self.peek() {
+            if !c.is_ascii_alphabetic() {
+                break;
+            }
+
+            let rest = &self.input[self.location..];
+            let length = rest
+                .find(|c: char| !c.is_ascii_alphabetic())
+                .unwrap_or(rest.len());
+            let name = rest[..length].to_string();
+            self.location += length;
+
+            let mut values = vec![];
+            while self.peek() == Some('[') {
+                self.location += 1;
+                values.push((self.location, self.value()?));
+            }
+            if values.is_empty() {
+                return Err(self.error(format!("property `{}` has no value", name)));
+            }
+
+            node.push(Property { name, values });
+        }
+        Ok(node)
+    }
+
+    fn value(&mut self) -> Result<String, ParseError> {
+        let mut value = String::new();
+        let mut chars = self.input[self.location..].char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                ']' => {
+                    self.location += i + 1;
+                    return Ok(value);
+                }
+                '\\' => {
+                    if let Some((_, escaped)) = chars.next() {
+                        value.push(escaped);
+                    }
+                }
+                _ => value.push(c),
+            }
+        }
+
+        self.location = self.input.len();
+        Err(self.error("unterminated property value".to_string()))
+    }
+}
+
+/// Reads a match from GNU Backgammon's SGF format. Comments, analysis and other properties
+/// that aren't part of the model are ignored.
+pub fn parse_sgf(input: &str) -> Result<Match, ParseError> {
+    let mut reader = Reader { input, location: 0 };
+    let trees = reader.collection()?;
+
+    let mut length = 0;
+    let mut games = vec![];
+    let mut crawford = vec![];
+    for (i, nodes) in trees.iter().enumerate() {
+        let info = read_game(input, i, nodes)?;
+        if i == 0 {
+            length = info.length;
+        }
+        crawford.push(info.crawford);
+        games.push(info.game);
+    }
+
+    let mut m = Match::make(length, games);
+    for (game, crawford) in m.games.iter_mut().zip(crawford) {
+        if let Some(crawford) = crawford {
+            game.crawford = crawford;
+        }
+    }
+    Ok(m)
+}
+
+struct GameInfo {
+    game: Game,
+    length: u32,
+    crawford: Option<bool>,
+}
+
+fn read_game(input: &str, index: usize, nodes: &[Node]) -> Result<GameInfo, ParseError> {
+    let error = |location: usize, message: String| ParseError::at(input, location, message);
+
+    let mut game_index = index as u32;
+    let mut length = 0;
+    let (mut o_player, mut x_player) = (String::new(), String::new());
+    let (mut o_score, mut x_score) = (0, 0);
+    let mut crawford = None;
+    let mut result = None;
+
+    let mut starting_side = None;
+    let mut turns: Vec<(Option<Action>, Option<Action>)> = vec![];
+    let mut position = Position::initial();
+    let mut cube = Cube::initial();
+
+    for property in nodes.iter().flatten() {
+        let (location, value) = &property.values[0];
+        let location = *location;
+
+        match property.name.as_str() {
+            "PB" => o_player = value.clone(),
+            "PW" => x_player = value.clone(),
+            "MI" => {
+                for (location, value) in &property.values {
+                    let (key, n) = match value.split_once(':') {
+                        Some(entry) => entry,
+                        None => continue,
+                    };
+                    let field = match key {
+                        "length" => &mut length,
+                        "game" => &mut game_index,
+                        "ws" => &mut x_score,
+                        "bs" => &mut o_score,
+                        _ => continue,
+                    };
+                    *field = n
+                        .parse()
+                        .map_err(|_| error(*location, format!("invalid `{}`", value)))?;
+                }
+            }
+            "RU" => crawford = Some(value.contains("CrawfordGame")),
+            "RE" => {
+                result = Some(
+                    parse_result(value)
+                        .ok_or_else(|| error(location, format!("invalid result `{}`", value)))?,
+                )
+            }
+            "B" | "W" => {
+                let side = if property.name == "B" {
+                    Side::O
+                } else {
+                    Side::X
+                };
+                let action = parse_action(value, side, &mut position, &cube)
+                    .ok_or_else(|| error(location, format!("invalid action `{}`", value)))?;
+                cube.apply(side, &action);
+                starting_side = starting_side.or(Some(side));
+
+                match (side, turns.last_mut()) {
+                    (Side::X, Some((_, x_action @ None))) => *x_action = Some(action),
+                    (Side::X, _) => turns.push((None, Some(action))),
+                    (Side::O, _) => turns.push((Some(action), None)),
+                }
+            }
+            _ => (),
+        }
+    }
+
+    let (winner, points) = result.map_or((None, 0), |(winner, points)| (Some(winner), points));
+    let game = Game {
+        number: game_index + 1,
+        o_player,
+        o_score,
+        x_player,
+        x_score,
+        winner,
+        points,
+        ..Game::make(starting_side, turns)
+    };
+
+    Ok(GameInfo {
+        game,
+        length,
+        crawford,
+    })
+}
+
+// E.g. `W+2`, or `B+1R` for a resignation.
+fn parse_result(value: &str) -> Option<(Side, u32)> {
+    let winner = match value.get(..2)? {
+        "B+" => Side::O,
+        "W+" => Side::X,
+        _ => return None,
+    };
+    let points = value[2..]
+        .trim_end_matches(char::is_alphabetic)
+        .parse()
+        .ok()?;
+    Some((winner, points))
+}
+
+// Keeps track of the position, to mark the hits that SGF doesn't record.
+fn parse_action(value: &str, side: Side, position: &mut Position, cube: &Cube) -> Option<Action> {
+    let action = match value.to_ascii_lowercase().as_str() {
+        "double" => Action::Double(2 * cube.value),
+        "take" => Action::Take,
+        "drop" => Action::Drop,
+        "beaver" => Action::Beaver(2 * cube.offered.unwrap_or(2 * cube.value)),
+        "accept" => Action::Accept,
+        "reject" => Action::Reject,
+        other => match other.strip_prefix("resign:") {
+            Some(points) => Action::Resign(Resignation::from_points(points.parse().ok()?)?),
+            None => Action::Move(parse_move(value, side, position)?),
+        },
+    };
+    Some(action)
+}
+
+// E.g. `52mhmk` for 5-2: 13/8 13/11.
+fn parse_move(value: &str, side: Side, position: &mut Position) -> Option<Move> {
+    let bytes = value.as_bytes();
+    if bytes.len() < 2 || !bytes.len().is_multiple_of(2) || !value.is_ascii() {
+        return None;
+    }
+
+    let die = |b: u8| match b {
+        b'1'..=b'6' => Some(b - b'0'),
+        _ => None,
+    };
+    let roll = Dice::make(die(bytes[0])?, die(bytes[1])?);
+
+    let mut view = match side {
+        Side::O => *position,
+        Side::X => position.flip(),
+    };
+    let mut checker_moves = vec![];
+    for pair in bytes[2..].chunks(2) {
+        let (from, to) = (point(pair[0])?, point(pair[1])?);
+        if !CheckerMove::make(from, to, false).is_valid() {
+            return None;
+        }
+        let hits = to != 0 && view.point_x_value(to - 1) == 1;
+        let checker_move = CheckerMove::make(from, to, hits);
+        view = view.try_apply_o_checker_move(&checker_move)?;
+        checker_moves.push(checker_move);
+    }
+
+    *position = match side {
+        Side::O => view,
+        Side::X => view.flip(),
+    };
+    Some(Move::make(roll, checker_moves))
+}
+
+// Points are letters from the point of view of the player moving: `a` is the 1-point, `y`
+// the bar and `z` off the board.
+fn point(letter: u8) -> Option<u8> {
+    match letter {
+        b'a'..=b'x' => Some(letter - b'a' + 1),
+        b'y' => Some(25),
+        b'z' => Some(0),
+        _ => None,
+    }
+}
+
+// Takes the 0-based points of `CheckerMove`, where the bar is 24.
+fn letter(point: u8) -> char {
+    char::from(b'a' + point)
+}
+
+/// Renders a match in GNU Backgammon's SGF format, one game tree per game.
+pub fn write_sgf(m: &Match) -> String {
+    let mut out = String::new();
+    for (i, game) in m.games.iter().enumerate() {
+        write!(
+            out,
+            "(;FF[4]GM[6]CA[UTF-8]MI[length:{}][game:{}][ws:{}][bs:{}]PB[{}]PW[{}]",
+            m.length,
+            i,
+            game.x_score,
+            game.o_score,
+            escape(&game.o_player),
+            escape(&game.x_player)
+        )
+        .unwrap();
+        if m.length > 0 {
+            let crawford = if game.crawford { ":CrawfordGame" } else { "" };
+            write!(out, "RU[Crawford{}]", crawford).unwrap();
+        }
+        if let Some(winner) = game.winner {
+            let resigned = matches!(
+                game.turns.last(),
+                Some((_, Some(Action::Resign(_) | Action::Accept)))
+                    | Some((Some(Action::Resign(_) | Action::Accept), None))
+            );
+            write!(
+                out,
+                "RE[{}+{}{}]",
+                color(winner),
+                game.points,
+                if resigned { "R" } else { "" }
+            )
+            .unwrap();
+        }
+        writeln!(out).unwrap();
+
+        for (o_action, x_action) in &game.turns {
+            for (side, action) in [(Side::O, o_action), (Side::X, x_action)] {
+                if let Some(action) = action {
+                    writeln!(out, ";{}[{}]", color(side), write_action(action)).unwrap();
+                }
+            }
+        }
+        writeln!(out, ")").unwrap();
+    }
+    out
+}
+
+fn write_action(action: &Action) -> String {
+    match action {
+        Action::Move(m) => {
+            let mut out = format!("{}{}", m.roll().high(), m.roll().low());
+            for checker_move in m.moves() {
+                out.push(letter(checker_move.src_point()));
+                out.push(if checker_move.is_bearing_off() {
+                    'z'
+                } else {
+                    letter(checker_move.dst_point())
+                });
+            }
+            out
+        }
+        Action::Double(_) => "double".to_string(),
+        Action::Take => "take".to_string(),
+        Action::Drop => "drop".to_string(),
+        Action::Beaver(_) => "beaver".to_string(),
+        Action::Resign(r) => format!("resign:{}", r.points()),
+        Action::Accept => "accept".to_string(),
+        Action::Reject => "reject".to_string(),
+    }
+}
+
+fn color(side: Side) -> char {
+    match side {
+        Side::O => 'B',
+        Side::X => 'W',
+    }
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace(']', "\\]")
+}
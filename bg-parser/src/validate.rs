@@ -0,0 +1,185 @@
+use std::fmt;
+
+use bg_core::game::{Action, Game, Match, Resignation};
+use bg_core::movegen::{generate_o_moves, generate_x_moves};
+use bg_core::moves::Move;
+use bg_core::position::{Position, Side};
+
+/// A problem found in a match that parsed fine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub game: u32,
+    /// The turn, unless the problem is with the game as a whole.
+    pub turn: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.turn {
+            Some(turn) => write!(f, "game {}, turn {}: {}", self.game, turn, self.message),
+            None => write!(f, "game {}: {}", self.game, self.message),
+        }
+    }
+}
+
+/// Replays every game, and reports illegal moves, wrong hit markers, and results or scores that
+/// don't add up. No diagnostics means the match is consistent.
+pub fn validate(m: &Match) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    let mut scores: Option<(u32, u32)> = None;
+
+    for game in &m.games {
+        validate_game(m, game, &mut diagnostics);
+
+        if let Some((o_score, x_score)) = scores {
+            if (o_score, x_score) != (game.o_score, game.x_score) {
+                diagnostics.push(Diagnostic {
+                    game: game.number,
+                    turn: None,
+                    message: format!(
+                        "the score should be {}-{} after the previous game",
+                        o_score, x_score
+                    ),
+                });
+            }
+        }
+
+        scores = match game.winner {
+            Some(Side::O) => Some((game.o_score + game.points, game.x_score)),
+            Some(Side::X) => Some((game.o_score, game.x_score + game.points)),
+            None => None,
+        };
+    }
+
+    diagnostics
+}
+
+fn validate_game(m: &Match, game: &Game, diagnostics: &mut Vec<Diagnostic>) {
+    let mut report = |turn: Option<usize>, message: String| {
+        diagnostics.push(Diagnostic {
+            game: game.number,
+            turn,
+            message,
+        })
+    };
+
+    let mut last = None;
+    let mut resignation: Option<Resignation> = None;
+
+    for step in game.replay() {
+        let step = match step {
+            Ok(step) => step,
+            Err(e) => {
+                report(
+                    Some(e.turn),
+                    format!("{} cannot play {}", side_name(e.side), e.action),
+                );
+                return;
+            }
+        };
+
+        match step.action {
+            Action::Move(m) => {
+                if !is_legal(&step.before, step.side, m, &step.after) {
+                    report(
+                        Some(step.turn),
+                        format!("{} plays {}, which is not legal", side_name(step.side), m),
+                    );
+                }
+                for message in check_hits(&step.before, step.side, m) {
+                    report(Some(step.turn), message);
+                }
+            }
+            Action::Resign(r) => resignation = Some(*r),
+            _ => (),
+        }
+
+        last = Some((step.action, step.after, step.cube));
+    }
+
+    let (action, position, cube) = match last {
+        Some(last) => last,
+        None => return,
+    };
+
+    let multiplier = match action {
+        Action::Move(_) if position.is_over() => {
+            if position.o_has_backgammoned() || position.x_has_backgammoned() {
+                3
+            } else if position.o_has_gammoned() || position.x_has_gammoned() {
+                2
+            } else {
+                1
+            }
+        }
+        Action::Drop => 1,
+        Action::Resign(_) | Action::Accept => resignation.map_or(1, |r| r.points()),
+        _ => {
+            report(None, "the game ends without a result".to_string());
+            return;
+        }
+    };
+
+    // The Jacoby rule may apply in money sessions.
+    let jacoby = m.length == 0 && cube.owner.is_none() && game.points == cube.value;
+    if game.points != cube.value * multiplier && !jacoby {
+        report(
+            None,
+            format!(
+                "the game is worth {} points, not {}",
+                cube.value * multiplier,
+                game.points
+            ),
+        );
+    }
+}
+
+fn is_legal(before: &Position, side: Side, m: &Move, after: &Position) -> bool {
+    let legal = match side {
+        Side::O => generate_o_moves(before, m.roll()),
+        Side::X => generate_x_moves(before, m.roll()),
+    };
+
+    if legal.is_empty() {
+        before == after
+    } else {
+        legal.contains(after)
+    }
+}
+
+fn check_hits(before: &Position, side: Side, m: &Move) -> Vec<String> {
+    let mut messages = vec![];
+    // Checker moves are numbered from the point of view of the player moving.
+    let mut position = match side {
+        Side::O => *before,
+        Side::X => before.flip(),
+    };
+
+    for checker_move in m.moves() {
+        let hits =
+            !checker_move.is_bearing_off() && position.point_x_value(checker_move.dst_point()) == 1;
+
+        if hits != checker_move.hits() {
+            let negation = if hits { "" } else { "not " };
+            messages.push(format!(
+                "{} should {}be marked as a hit",
+                checker_move, negation
+            ));
+        }
+
+        position = match position.try_apply_o_checker_move(checker_move) {
+            Some(next) => next,
+            None => break,
+        };
+    }
+
+    messages
+}
+
+fn side_name(side: Side) -> &'static str {
+    match side {
+        Side::O => "o",
+        Side::X => "x",
+    }
+}
@@ -0,0 +1,93 @@
+use std::fmt::Write;
+
+use bg_core::game::{Action, Game, Match};
+use bg_core::moves::Move;
+use bg_core::position::Side;
+
+// Where the `x` column starts, as in the files we read.
+const X_COLUMN: usize = 37;
+
+/// Renders a match in the format `parse_match` reads.
+pub fn write_match(m: &Match) -> String {
+    let mut out = String::new();
+    for (key, value) in &m.metadata {
+        let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+        writeln!(out, "; [{} \"{}\"]", key, value).unwrap();
+    }
+    // Money sessions have no header.
+    if m.length > 0 {
+        if !m.metadata.is_empty() {
+            writeln!(out).unwrap();
+        }
+        writeln!(out, " {} point match", m.length).unwrap();
+    }
+
+    for (i, game) in m.games.iter().enumerate() {
+        if i > 0 || m.length > 0 || !m.metadata.is_empty() {
+            writeln!(out).unwrap();
+        }
+        out.push_str(&write_game(game));
+    }
+
+    // The footer of the last game carries the end of the match, once it's over.
+    if m.winner().is_some() {
+        out.pop();
+        out.push_str(" and the match\n");
+    }
+    out
+}
+
+/// Renders a single game: its header, its turns in two columns, and the points won if it's
+/// over.
+pub fn write_game(game: &Game) -> String {
+    let mut out = String::new();
+    let crawford = if game.crawford { " (Crawford)" } else { "" };
+    writeln!(out, " Game {}{}", game.number, crawford).unwrap();
+    write_line(
+        &mut out,
+        &format!(" {} : {}", game.o_player, game.o_score),
+        &format!("{} : {}", game.x_player, game.x_score),
+    );
+
+    for (i, (o_action, x_action)) in game.turns.iter().enumerate() {
+        let o_action = o_action.as_ref().map_or(String::new(), write_action);
+        let x_action = x_action.as_ref().map_or(String::new(), write_action);
+        write_line(&mut out, &format!("{:3}) {}", i + 1, o_action), &x_action);
+    }
+
+    let footer = if game.points == 1 {
+        "Wins 1 point".to_string()
+    } else {
+        format!("Wins {} points", game.points)
+    };
+    match game.winner {
+        Some(Side::O) => writeln!(out, "      {}", footer).unwrap(),
+        Some(Side::X) => writeln!(out, "{:width$} {}", "", footer, width = X_COLUMN).unwrap(),
+        None => (),
+    }
+
+    out
+}
+
+// Long entries in the `o` column push the `x` column right, but are always separated from it.
+fn write_line(out: &mut String, left: &str, right: &str) {
+    let line = format!("{:width$} {}", left, right, width = X_COLUMN - 1);
+    writeln!(out, "{}", line.trim_end()).unwrap();
+}
+
+fn write_action(action: &Action) -> String {
+    match action {
+        Action::Move(m) => write_move(m),
+        // Cube and resignation actions are indented by one more space.
+        _ => format!(" {}", action),
+    }
+}
+
+// The roll is written as two digits, e.g. `31:`, unlike its `Display`.
+fn write_move(m: &Move) -> String {
+    let mut out = format!("{}{}:", m.roll().high(), m.roll().low());
+    for checker_move in m.moves() {
+        write!(out, " {}", checker_move).unwrap();
+    }
+    out
+}
@@ -0,0 +1,172 @@
+mod service;
+
+pub use service::{Limits, Response, Service};
+
+use std::io::{Cursor, Read};
+use std::thread;
+
+use bg_core::eval::Evaluator;
+use tiny_http::{Header, Request, Server};
+
+/// Answers requests on `server` with `threads` workers, until it's unblocked.
+pub fn serve<E: Evaluator + Sync>(service: &Service<E>, server: &Server, threads: usize) {
+    thread::scope(|scope| {
+        for _ in 0..threads.max(1) {
+            scope.spawn(move || {
+                while let Ok(request) = server.recv() {
+                    respond(service, request);
+                }
+            });
+        }
+    });
+}
+
+fn respond<E: Evaluator>(service: &Service<E>, mut request: Request) {
+    let max_body = service.limits().max_body;
+    let mut body = String::new();
+    let (status, json) = if request.body_length().is_some_and(|n| n > max_body) {
+        service::error(413, "request too large")
+    } else {
+        // Reading one byte more than allowed tells bodies without a length that are too large.
+        let read = request
+            .as_reader()
+            .take(max_body as u64 + 1)
+            .read_to_string(&mut body);
+        match read {
+            Ok(_) => {
+                let path = request.url().split('?').next().unwrap_or_default();
+                service.handle(request.method().as_str(), path, &body)
+            }
+            Err(_) => service::error(400, "the body must be UTF-8"),
+        }
+    };
+
+    let json = json.to_string();
+    let response = tiny_http::Response::new(
+        status.into(),
+        vec![Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap()],
+        Cursor::new(json.as_bytes()),
+        Some(json.len()),
+        None,
+    );
+    // The client may be gone, and there's no one else to tell.
+    let _ = request.respond(response);
+}
+
+#[cfg(test)]
+use bg_core::testing::RaceEvaluator;
+#[cfg(test)]
+use serde_json::{json, Value};
+#[cfg(test)]
+use std::io::Write;
+#[cfg(test)]
+use std::net::TcpStream;
+#[cfg(test)]
+use std::sync::Arc;
+
+#[cfg(test)]
+fn post(service: &Service<RaceEvaluator>, path: &str, body: Value) -> Response {
+    service.handle("POST", path, &body.to_string())
+}
+
+#[test]
+fn test_endpoints() {
+    let service = Service::make(RaceEvaluator, Limits::default());
+    let bearoff = json!({ "o_points": [[1, 2]], "x_points": [[24, 15]] });
+
+    let (status, moves) = post(
+        &service,
+        "/moves",
+        json!({ "position": bearoff, "dice": [2, 1] }),
+    );
+    assert_eq!(200, status);
+    assert_eq!(
+        json!([{ "move": "1/0 1/0", "position": "AAAA/v8AAAAAAA" }]),
+        moves["moves"]
+    );
+
+    let request = json!({ "position": "4HPwATDgc/ABMA", "dice": [3, 1], "count": 2 });
+    let (status, hint) = post(&service, "/hint", request);
+    assert_eq!(200, status);
+    assert_eq!(2, hint["plays"].as_array().unwrap().len());
+    assert!(hint["plays"][0]["equity"].as_f64() >= hint["plays"][1]["equity"].as_f64());
+
+    let request = json!({ "position": bearoff, "score": { "away": 3, "opponent_away": 5 } });
+    let (status, cube) = post(&service, "/cube", request);
+    assert_eq!(200, status);
+    assert_eq!("double_pass", cube["action"]);
+
+    let (status, rollout) = post(&service, "/rollout", json!({ "position": bearoff }));
+    assert_eq!(200, status);
+    assert_eq!(1000, rollout["games"]);
+    assert_eq!(1.0, rollout["evaluation"]["win"]);
+}
+
+#[test]
+fn test_errors_and_limits() {
+    let limits = Limits {
+        max_games: 100,
+        ..Limits::default()
+    };
+    let service = Service::make(RaceEvaluator, limits);
+    let initial = json!("4HPwATDgc/ABMA");
+
+    assert_eq!(404, service.handle("POST", "/evaluate", "{}").0);
+    assert_eq!(405, service.handle("GET", "/moves", "").0);
+    assert_eq!(400, service.handle("POST", "/moves", "[").0);
+    let overlapping = json!({ "o_points": [[6, 5]], "x_points": [[6, 5]] });
+    let (status, error) = post(&service, "/moves", json!({ "position": overlapping }));
+    assert_eq!(
+        (400, json!("invalid position")),
+        (status, error["error"].clone())
+    );
+    let request = json!({ "position": initial, "dice": [7, 1] });
+    assert_eq!(400, post(&service, "/hint", request).0);
+    let request = json!({ "position": initial, "plies": 2 });
+    assert_eq!(400, post(&service, "/cube", request).0);
+    let request = json!({ "position": initial, "games": 101 });
+    assert_eq!(400, post(&service, "/rollout", request).0);
+
+    let large = format!("{{\"position\": \"{}\"}}", " ".repeat(20 * 1024));
+    assert_eq!(413, service.handle("POST", "/rollout", &large).0);
+}
+
+#[test]
+fn test_serve() {
+    let limits = Limits {
+        max_evaluations: 0,
+        ..Limits::default()
+    };
+    let service = Arc::new(Service::make(RaceEvaluator, limits));
+    let server = Arc::new(Server::http("127.0.0.1:0").unwrap());
+    let address = server.server_addr().to_ip().unwrap();
+    let worker = {
+        let (service, server) = (service.clone(), server.clone());
+        thread::spawn(move || serve(&service, &server, 2))
+    };
+
+    let request = |body: &str| {
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(
+            stream,
+            "POST /rollout HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\n\
+             Connection: close\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    };
+
+    let response = request(r#"{"position": "4HPwATDgc/ABMA"}"#);
+    assert!(response.starts_with("HTTP/1.1 503"));
+    assert!(response.ends_with(r#"{"error":"too many evaluations in progress"}"#));
+    let response = request(&format!(r#"{{"position": "{}"}}"#, " ".repeat(20 * 1024)));
+    assert!(response.starts_with("HTTP/1.1 413"));
+
+    server.unblock();
+    server.unblock();
+    worker.join().unwrap();
+}
@@ -0,0 +1,37 @@
+use std::env;
+
+use bg_core::bearoff::{BearoffDatabase, BearoffEvaluator};
+use bg_core::eval::{Evaluator, RolloutEvaluator};
+use bg_server::{serve, Limits, Service};
+use tiny_http::Server;
+
+const THREADS: usize = 4;
+
+// Usage: `bg-server <port> [bearoff database]`
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let port: u16 = args
+        .get(1)
+        .and_then(|a| a.parse().ok())
+        .expect("Usage: bg-server <port> [bearoff database]");
+
+    match args.get(2) {
+        Some(path) => {
+            let database =
+                BearoffDatabase::load(path).expect("Something went wrong reading the file.");
+            run(port, BearoffEvaluator::make(database, RolloutEvaluator));
+        }
+        None => run(port, RolloutEvaluator),
+    }
+}
+
+fn run<E: Evaluator + Sync>(port: u16, evaluator: E) {
+    let server = Server::http(("127.0.0.1", port)).expect("Could not listen on the port.");
+    println!("Listening on http://127.0.0.1:{}", port);
+
+    serve(
+        &Service::make(evaluator, Limits::default()),
+        &server,
+        THREADS,
+    );
+}
@@ -0,0 +1,410 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use bg_core::cube::{position_cube_decision, CubeAction, CubeContext};
+use bg_core::dice::Dice;
+use bg_core::eval::{CubeOwnership, Evaluation, Evaluator};
+use bg_core::met::{MatchEquityTable, MatchScore};
+use bg_core::movegen::{find_o_move, generate_o_moves};
+use bg_core::position::Position;
+use bg_core::rollout::rollout_o_games_with;
+use bg_core::search::{rank_o_plays, SearchConfig};
+use serde_json::{json, Map, Value};
+
+/// How much work a single request may ask for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// The largest request body, in bytes.
+    pub max_body: usize,
+    /// The most games in one rollout.
+    pub max_games: u32,
+    /// The deepest search for hints and cube decisions.
+    pub max_plies: u8,
+    /// The most hints, cube decisions and rollouts running at once. Others are turned away
+    /// until one finishes, so that long ones can't take every worker.
+    pub max_evaluations: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits {
+            max_body: 16 * 1024,
+            max_games: 100_000,
+            max_plies: 1,
+            max_evaluations: 2,
+        }
+    }
+}
+
+/// An HTTP status and a JSON body.
+pub type Response = (u16, Value);
+
+type Handler<E> = fn(&Service<E>, &Map<String, Value>) -> Result<Value, Response>;
+
+/// Answers the analysis requests, whatever serves them.
+///
+/// Every endpoint takes a JSON object with a `position`, with `o` on roll: either a GNUbg
+/// position ID, or `{"o_points": [[6, 5], ...], "x_points": [[1, 2], ...], "o_bar": 0,
+/// "x_bar": 0}` with points numbered from `o`'s side and the checkers not listed borne off.
+///
+/// - `POST /moves` with `dice`, e.g. `[3, 1]`: the legal plays.
+/// - `POST /hint` with `dice`, and optionally `plies` and `count`: the plays, best first.
+/// - `POST /cube`, optionally with `cube` (`{"value": 2, "owner": "player"}`) and `score`
+///   (`{"away": 3, "opponent_away": 5, "crawford": false}`): the cube decision.
+/// - `POST /rollout`, optionally with `games`: the outcome of cubeless games from the position,
+///   with both sides making the plays the evaluator prefers.
+pub struct Service<E: Evaluator> {
+    evaluator: E,
+    limits: Limits,
+    met: MatchEquityTable,
+    evaluations: AtomicUsize,
+}
+
+impl<E: Evaluator> Service<E> {
+    pub fn make(evaluator: E, limits: Limits) -> Service<E> {
+        Service {
+            evaluator,
+            limits,
+            met: MatchEquityTable::built_in(),
+            evaluations: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// Answers a request, given its method, path and body.
+    pub fn handle(&self, method: &str, path: &str, body: &str) -> Response {
+        if body.len() > self.limits.max_body {
+            return error(413, "request too large");
+        }
+
+        let handler: Handler<E> = match path {
+            "/moves" => Service::moves,
+            "/hint" => Service::hint,
+            "/cube" => Service::cube,
+            "/rollout" => Service::rollout,
+            _ => return error(404, "not found"),
+        };
+        if method != "POST" {
+            return error(405, "method not allowed");
+        }
+
+        let request = match serde_json::from_str(body) {
+            Ok(Value::Object(request)) => request,
+            Ok(_) => return bad_request("expected a JSON object"),
+            Err(e) => return bad_request(&format!("invalid JSON: {}", e)),
+        };
+        match handler(self, &request) {
+            Ok(response) => (200, response),
+            Err(response) => response,
+        }
+    }
+
+    fn moves(&self, request: &Map<String, Value>) -> Result<Value, Response> {
+        let position = position(request)?;
+        let dice = dice(request)?;
+        let mut plays: Vec<Position> = generate_o_moves(&position, &dice).into_iter().collect();
+        plays.sort_by_cached_key(|p| p.id());
+        let plays: Vec<Value> = plays
+            .iter()
+            .map(|play| play_json(&position, &dice, play))
+            .collect();
+        Ok(json!({ "moves": plays }))
+    }
+
+    fn hint(&self, request: &Map<String, Value>) -> Result<Value, Response> {
+        let position = position(request)?;
+        let dice = dice(request)?;
+        let plies = self.plies(request)?;
+        let count = match request.get("count") {
+            None => usize::MAX,
+            Some(count) => count
+                .as_u64()
+                .filter(|&c| c > 0)
+                .ok_or_else(|| bad_request("`count` must be a positive integer"))?
+                as usize,
+        };
+
+        let config = SearchConfig {
+            plies,
+            ..SearchConfig::default()
+        };
+        let _slot = self.slot()?;
+        let plays: Vec<Value> = rank_o_plays(&self.evaluator, &position, &dice, &config)
+            .iter()
+            .take(count)
+            .map(|play| {
+                let mut json = play_json(&position, &dice, &play.position);
+                json["equity"] = json!(play.equity);
+                json["ply"] = json!(play.ply);
+                json["evaluation"] = evaluation_json(&play.evaluation);
+                json
+            })
+            .collect();
+        Ok(json!({ "plays": plays }))
+    }
+
+    fn cube(&self, request: &Map<String, Value>) -> Result<Value, Response> {
+        let position = position(request)?;
+        let plies = self.plies(request)?;
+        let (value, owner) = cube(request)?;
+        let context = match score(request)? {
+            Some(score) => CubeContext::match_play(value, owner, score, &self.met),
+            None => CubeContext::money(value, owner),
+        };
+        if !context.can_double() {
+            return Err(bad_request("the player on roll cannot double"));
+        }
+
+        let _slot = self.slot()?;
+        let decision = position_cube_decision(&self.evaluator, &position, plies, &context);
+        let action = match decision.action {
+            CubeAction::NoDouble => "no_double",
+            CubeAction::DoubleTake => "double_take",
+            CubeAction::DoublePass => "double_pass",
+            CubeAction::TooGood => "too_good",
+        };
+        Ok(json!({
+            "action": action,
+            "no_double": decision.no_double,
+            "double_take": decision.double_take,
+            "double_pass": decision.double_pass,
+            "take_point": decision.take_point,
+        }))
+    }
+
+    fn rollout(&self, request: &Map<String, Value>) -> Result<Value, Response> {
+        let position = position(request)?;
+        let games = match request.get("games") {
+            None => u64::from(self.limits.max_games.min(1000)),
+            Some(games) => games
+                .as_u64()
+                .filter(|&g| g > 0 && g <= u64::from(self.limits.max_games))
+                .ok_or_else(|| {
+                    bad_request(&format!(
+                        "`games` must be from 1 to {}",
+                        self.limits.max_games
+                    ))
+                })?,
+        };
+
+        let _slot = self.slot()?;
+        let stats = rollout_o_games_with(&self.evaluator, &position, games);
+        let evaluation = Evaluation::make(
+            stats.o_win,
+            stats.o_gammon,
+            stats.o_backgammon,
+            stats.x_gammon,
+            stats.x_backgammon,
+        );
+        Ok(json!({
+            "games": games,
+            "evaluation": evaluation_json(&evaluation),
+        }))
+    }
+
+    fn slot(&self) -> Result<Slot<'_>, Response> {
+        Slot::take(&self.evaluations, self.limits.max_evaluations)
+            .ok_or_else(|| error(503, "too many evaluations in progress"))
+    }
+
+    fn plies(&self, request: &Map<String, Value>) -> Result<u8, Response> {
+        match request.get("plies") {
+            None => Ok(0),
+            Some(plies) => plies
+                .as_u64()
+                .filter(|&p| p <= u64::from(self.limits.max_plies))
+                .map(|p| p as u8)
+                .ok_or_else(|| {
+                    bad_request(&format!(
+                        "`plies` must be from 0 to {}",
+                        self.limits.max_plies
+                    ))
+                }),
+        }
+    }
+}
+
+// One of the limited evaluations running at once, released when dropped.
+struct Slot<'a> {
+    running: &'a AtomicUsize,
+}
+
+impl<'a> Slot<'a> {
+    fn take(running: &'a AtomicUsize, max: usize) -> Option<Slot<'a>> {
+        running
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+                if n < max {
+                    Some(n + 1)
+                } else {
+                    None
+                }
+            })
+            .ok()
+            .map(|_| Slot { running })
+    }
+}
+
+impl Drop for Slot<'_> {
+    fn drop(&mut self) {
+        self.running.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+pub(crate) fn error(status: u16, message: &str) -> Response {
+    (status, json!({ "error": message }))
+}
+
+fn bad_request(message: &str) -> Response {
+    error(400, message)
+}
+
+fn position(request: &Map<String, Value>) -> Result<Position, Response> {
+    match request.get("position") {
+        Some(Value::String(id)) => {
+            Position::from_id(id).ok_or_else(|| bad_request("invalid position ID"))
+        }
+        Some(Value::Object(checkers)) => {
+            checkers_position(checkers).ok_or_else(|| bad_request("invalid position"))
+        }
+        _ => Err(bad_request("expected a `position` ID or object")),
+    }
+}
+
+fn checkers_position(checkers: &Map<String, Value>) -> Option<Position> {
+    let points = |key: &str| -> Option<Vec<(u8, u8)>> {
+        let points = match checkers.get(key) {
+            None => return Some(vec![]),
+            Some(points) => points.as_array()?,
+        };
+        points
+            .iter()
+            .map(|point| match point.as_array()?.as_slice() {
+                [p, n] => Some((small(p, 1, 24)?, small(n, 1, 15)?)),
+                _ => None,
+            })
+            .collect()
+    };
+    let bar = |key: &str| checkers.get(key).map_or(Some(0), |n| small(n, 0, 15));
+
+    let (o_points, x_points) = (points("o_points")?, points("x_points")?);
+    let (o_bar, x_bar) = (bar("o_bar")?, bar("x_bar")?);
+    let on_board = |points: &[(u8, u8)], bar: u8| {
+        points.iter().map(|(_, n)| u32::from(*n)).sum::<u32>() + u32::from(bar)
+    };
+    let (o_count, x_count) = (on_board(&o_points, o_bar), on_board(&x_points, x_bar));
+
+    let mut occupied = [false; 25];
+    for (point, _) in o_points.iter().chain(x_points.iter()) {
+        if std::mem::replace(&mut occupied[usize::from(*point)], true) {
+            return None;
+        }
+    }
+    if o_count > 15 || x_count > 15 {
+        return None;
+    }
+
+    Some(Position::make(
+        &o_points,
+        &x_points,
+        o_bar,
+        x_bar,
+        15 - o_count as u8,
+        15 - x_count as u8,
+    ))
+}
+
+fn small(value: &Value, min: u8, max: u8) -> Option<u8> {
+    value
+        .as_u64()
+        .filter(|v| (u64::from(min)..=u64::from(max)).contains(v))
+        .map(|v| v as u8)
+}
+
+fn dice(request: &Map<String, Value>) -> Result<Dice, Response> {
+    let dice = request
+        .get("dice")
+        .and_then(|d| d.as_array())
+        .map(|d| d.iter().map(|die| small(die, 1, 6)).collect::<Vec<_>>());
+    match dice.as_deref() {
+        Some([Some(first), Some(second)]) => Ok(Dice::make(*first, *second)),
+        _ => Err(bad_request("expected `dice`, two numbers from 1 to 6")),
+    }
+}
+
+fn cube(request: &Map<String, Value>) -> Result<(u32, CubeOwnership), Response> {
+    let cube = match request.get("cube") {
+        None => return Ok((1, CubeOwnership::Centered)),
+        Some(Value::Object(cube)) => cube,
+        Some(_) => return Err(bad_request("expected a `cube` object")),
+    };
+
+    let value = match cube.get("value") {
+        None => 1,
+        Some(value) => value
+            .as_u64()
+            .filter(|v| v.is_power_of_two() && *v <= 1 << 16)
+            .ok_or_else(|| bad_request("the cube value must be a power of 2"))?
+            as u32,
+    };
+    let owner = match cube.get("owner").and_then(|o| o.as_str()) {
+        None | Some("centered") => CubeOwnership::Centered,
+        Some("player") => CubeOwnership::Player,
+        Some("opponent") => CubeOwnership::Opponent,
+        Some(_) => {
+            return Err(bad_request(
+                "the cube owner must be `centered`, `player` or `opponent`",
+            ))
+        }
+    };
+    Ok((value, owner))
+}
+
+fn score(request: &Map<String, Value>) -> Result<Option<MatchScore>, Response> {
+    let score = match request.get("score") {
+        None | Some(Value::Null) => return Ok(None),
+        Some(Value::Object(score)) => score,
+        Some(_) => return Err(bad_request("expected a `score` object")),
+    };
+
+    let away = |key: &str| {
+        score
+            .get(key)
+            .and_then(|a| a.as_u64())
+            .filter(|&a| a > 0 && a < 1 << 16)
+            .map(|a| a as u32)
+            .ok_or_else(|| bad_request(&format!("`{}` must be a positive integer", key)))
+    };
+    let crawford = score
+        .get("crawford")
+        .map_or(Some(false), |c| c.as_bool())
+        .ok_or_else(|| bad_request("`crawford` must be a boolean"))?;
+    Ok(Some(MatchScore::make(
+        away("away")?,
+        away("opponent_away")?,
+        crawford,
+    )))
+}
+
+// A play, as the checker moves and the resulting position ID with `x` on roll.
+fn play_json(from: &Position, dice: &Dice, to: &Position) -> Value {
+    let moves: Vec<String> = find_o_move(from, dice, to)
+        .map(|m| m.moves().iter().map(|c| c.to_string()).collect())
+        .unwrap_or_default();
+    json!({
+        "move": moves.join(" "),
+        "position": to.flip().id(),
+    })
+}
+
+fn evaluation_json(evaluation: &Evaluation) -> Value {
+    json!({
+        "win": evaluation.win,
+        "win_gammon": evaluation.win_gammon,
+        "win_backgammon": evaluation.win_backgammon,
+        "lose_gammon": evaluation.lose_gammon,
+        "lose_backgammon": evaluation.lose_backgammon,
+        "equity": evaluation.equity(),
+    })
+}